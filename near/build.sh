@@ -1,5 +1,9 @@
 #!/bin/bash
 set -e
 cd "`dirname $0`"
+mkdir -p ./res
+# one contract with the logic of `node_evaluation`, built first as it shares the name of `msg_verify.wasm`
+cargo build -p msg-verify --target wasm32-unknown-unknown --release --features single-contract
+cp target/wasm32-unknown-unknown/release/msg_verify.wasm ./res/msg_verify_single.wasm
 cargo build --all --target wasm32-unknown-unknown --release
-mkdir -p ./res && cp target/wasm32-unknown-unknown/release/*.wasm ./res/
\ No newline at end of file
+cp target/wasm32-unknown-unknown/release/*.wasm ./res/
\ No newline at end of file
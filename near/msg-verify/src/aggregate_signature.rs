@@ -0,0 +1,271 @@
+use crate::*;
+use node_evaluation::bls;
+
+#[cfg(not(feature = "single-contract"))]
+const GAS_FOR_GET_BLS_COMMITTEE: Gas = Gas(20_000_000_000_000);
+
+#[near_bindgen]
+impl Contract {
+    /// @notice Verify a cross-chain message signed by the validators of the current epoch with one BLS aggregate signature.
+    ///
+    /// @dev Cross contract call to get the BLS committee of the current epoch from `node_evaluation contract`,
+    /// read directly when built with the feature `single-contract`.
+    /// The weight is the credibility of the signers in the snapshot over the credibility of the whole snapshot.
+    /// Signers are trusted and the other validators of the snapshot are reported as absent.
+    /// The signers must also satisfy the quorum of the policy resolved for the message, see `resolve_policy`,
+    /// otherwise they are reported as an exception.
+    /// A message with `sqos.reveal` is rejected, it is only verified in a commit-reveal session.
+    ///
+    /// @param message The message to be verified.
+    /// @param percentage [0~10000]. Minimum percent of weights of the signers, not lower than the threshold of the policy.
+    /// @param id The id of the message in `cross-chain protocol contract`.
+    /// @param epoch The epoch of the validator set the bitmap refers to.
+    /// @param signature The aggregate signature over `attestation_payload(id, message)`.
+    /// @param signers Bitmap over the validators of the snapshot, bit `i` is `signers[i / 8] >> (i % 8) & 1`.
+    ///
+    /// @return The result of the verification. The `Vec` will be empty if failed.
+    pub fn msg_verify_aggregate(
+        &mut self,
+        message: Message,
+        percentage: Option<u32>,
+        id: u64,
+        epoch: u64,
+        signature: Base64VecU8,
+        signers: Base64VecU8,
+    ) -> PromiseOrValue<Vec<Message>> {
+        assert_eq!(env::predecessor_account_id(), self.cross_contract_id);
+        require!(percentage.unwrap_or(0) <= 10000, "percentage out of range");
+        require!(!message.sqos.reveal, "message requires commit-reveal");
+        self.request_committee(message, percentage, id, epoch, signature, signers)
+    }
+
+    #[private]
+    pub fn aggregate_callback(
+        &mut self,
+        message: Message,
+        percentage: Option<u32>,
+        id: u64,
+        epoch: u64,
+        signature: Base64VecU8,
+        signers: Base64VecU8,
+    ) -> Vec<Message> {
+        require!(env::promise_results_count() == 1);
+        let committee = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                near_sdk::serde_json::from_slice::<Option<BlsCommittee>>(&result)
+                    .unwrap_or_else(|_| env::panic_str("in callback!, `from_slice` error!"))
+            }
+            _ => env::panic_str("in callback!, but params error!"),
+        };
+        self.conclude_aggregate(
+            committee, message, percentage, id, epoch, signature, signers,
+        )
+    }
+}
+
+impl Contract {
+    /// Verify the aggregate signature against the BLS committee of `node_evaluation` built in this contract
+    #[cfg(feature = "single-contract")]
+    #[allow(clippy::too_many_arguments)]
+    fn request_committee(
+        &mut self,
+        message: Message,
+        percentage: Option<u32>,
+        id: u64,
+        epoch: u64,
+        signature: Base64VecU8,
+        signers: Base64VecU8,
+    ) -> PromiseOrValue<Vec<Message>> {
+        let committee = self.evaluation().get_current_bls_committee();
+        PromiseOrValue::Value(self.conclude_aggregate(
+            committee, message, percentage, id, epoch, signature, signers,
+        ))
+    }
+
+    /// Get the BLS committee from `node_evaluation contract` to verify the aggregate signature in `aggregate_callback`
+    #[cfg(not(feature = "single-contract"))]
+    #[allow(clippy::too_many_arguments)]
+    fn request_committee(
+        &mut self,
+        message: Message,
+        percentage: Option<u32>,
+        id: u64,
+        epoch: u64,
+        signature: Base64VecU8,
+        signers: Base64VecU8,
+    ) -> PromiseOrValue<Vec<Message>> {
+        ext_ec::get_current_bls_committee(
+            self.node_ev_address.clone(),
+            NO_DEPOSIT,
+            GAS_FOR_GET_BLS_COMMITTEE,
+        )
+        .then(ext_self::aggregate_callback(
+            message,
+            percentage,
+            id,
+            epoch,
+            signature,
+            signers,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            Gas(env::prepaid_gas()
+                .0
+                .saturating_sub(GAS_FOR_GET_BLS_COMMITTEE.0)
+                .saturating_sub(GAS_FOR_MSG_VERIFY.0)),
+        ))
+        .into()
+    }
+
+    /// Verify the aggregate signature against `committee`, then audit and deliver the report
+    #[allow(clippy::too_many_arguments)]
+    fn conclude_aggregate(
+        &mut self,
+        committee: Option<BlsCommittee>,
+        message: Message,
+        percentage: Option<u32>,
+        id: u64,
+        epoch: u64,
+        signature: Base64VecU8,
+        signers: Base64VecU8,
+    ) -> Vec<Message> {
+        let report = self.verify_aggregate(
+            committee, message, percentage, id, epoch, signature, signers,
+        );
+        self.record_audit(Some(id), &report);
+        self.deliver(Some(id), &report);
+        report.into_messages()
+    }
+
+    /// Weigh the signers of a valid aggregate signature and report the behavior to `node_evaluation contract`
+    #[allow(clippy::too_many_arguments)]
+    fn verify_aggregate(
+        &mut self,
+        committee: Option<BlsCommittee>,
+        message: Message,
+        percentage: Option<u32>,
+        id: u64,
+        epoch: u64,
+        signature: Base64VecU8,
+        signers: Base64VecU8,
+    ) -> VerificationReport {
+        let committee = committee.unwrap_or_else(|| env::panic_str("no validator set selected"));
+        #[cfg(not(feature = "single-contract"))]
+        self.observe_epoch(committee.epoch);
+        require!(committee.epoch == epoch, "epoch mismatch");
+        let indexes = bls::bitmap_indexes(&signers.0, committee.validators.len())
+            .unwrap_or_else(|| env::panic_str("invalid signer bitmap"));
+
+        let mut public_keys: Vec<&[u8]> = Vec::new();
+        let mut signed_credibility: u64 = 0;
+        for index in indexes.iter() {
+            let public_key = committee.bls_public_keys[*index]
+                .as_ref()
+                .unwrap_or_else(|| env::panic_str("signer without BLS public key"));
+            public_keys.push(&public_key.0);
+            signed_credibility += committee.validators[*index].credibility_value as u64;
+        }
+        if !bls::fast_aggregate_verify(
+            &public_keys,
+            &attestation_payload(id, &message),
+            &signature.0,
+        ) {
+            emit_event(
+                "invalid_signature",
+                json!([{ "id": id, "aggregate": true }]),
+            );
+            return VerificationReport::new(VerificationOutcome::InvalidSignature);
+        }
+
+        let policy = self.resolve_policy(message.clone());
+        let threshold = self.resolve_threshold(percentage, &policy);
+        let message_hash = message.to_hash(Some(id));
+        // validators of the committee who did not sign vote for no copy
+        let votes: Vec<Vote> = committee
+            .validators
+            .iter()
+            .zip(committee.operators.iter())
+            .enumerate()
+            .map(|(index, (vc, operator))| Vote {
+                validator: vc.validator.clone(),
+                message_hash: if indexes.contains(&index) {
+                    message_hash.clone()
+                } else {
+                    String::new()
+                },
+                credibility: vc.credibility_value,
+                stake: self.stake_of(&vc.validator, &policy.voting_power),
+                operator: operator.clone(),
+            })
+            .collect();
+        let tally = voting::tally(&votes, &policy.voting_power);
+        let (signed_power, credibility_weight) = tally
+            .groups
+            .iter()
+            .find(|group| group.message_hash == message_hash)
+            .map_or((0, 0), |group| (group.power, group.weight));
+        let mut report = VerificationReport::new(VerificationOutcome::ThresholdNotMet);
+        report.message_hash = Some(message_hash.clone());
+        report.total_credibility = tally.total_credibility;
+        report.total_power = U128(tally.total_power);
+        report.threshold = threshold;
+        report.snapshot = committee.validators.clone();
+        log!(
+            "credibility_weight: {}, credibility_weight_threshold: {}",
+            credibility_weight,
+            threshold
+        );
+        let mut operators: HashMap<PublicKey, AccountId> = HashMap::new();
+        for (vc, operator) in committee.validators.iter().zip(committee.operators) {
+            if let Some(operator) = operator {
+                operators.insert(vc.validator.clone(), operator);
+            }
+        }
+        let mut signed: Vec<NodeCredibility> = Vec::new();
+        let mut absent: Vec<PublicKey> = Vec::new();
+        for (index, vc) in committee.validators.into_iter().enumerate() {
+            if indexes.contains(&index) {
+                signed.push(vc);
+            } else {
+                absent.push(vc.validator);
+            }
+        }
+        let signed_keys: Vec<PublicKey> = signed.iter().map(|vc| vc.validator.clone()).collect();
+        report.groups.push(GroupReport {
+            message_hash,
+            validators: signed_keys.clone(),
+            operators: distinct_operators(&signed_keys, &operators),
+            credibility: signed_credibility,
+            power: U128(signed_power),
+            credibility_weight,
+        });
+        let mut trusted: Vec<PublicKey> = Vec::new();
+        let mut exeception: Vec<(Vec<PublicKey>, u32)> = Vec::new();
+        if credibility_weight < threshold {
+            exeception.push((signed_keys, credibility_weight));
+        } else if !policy.is_satisfied_by(&report.groups[0]) {
+            report.outcome = VerificationOutcome::QuorumNotMet;
+            log!(
+                "quorum of the policy not reached, validators: {}, operators: {}, credibility: {}",
+                signed_keys.len(),
+                report.groups[0].operators,
+                signed_credibility
+            );
+            exeception.push((signed_keys, credibility_weight));
+        } else {
+            report.outcome = VerificationOutcome::Verified;
+            report.message = Some(message.clone());
+            trusted = signed_keys;
+            self.record_verified(Some(id), message, threshold, signed, true);
+        }
+        self.send_scoring(
+            Scoring {
+                trusted,
+                exeception,
+                absent,
+                ..Default::default()
+            },
+            self.delivery_gas(),
+        );
+        report
+    }
+}
@@ -0,0 +1,209 @@
+use crate::*;
+
+const DEFAULT_AUDIT_RETENTION: u64 = 1000;
+// entries pruned by one verification at most, so lowering the retention does not exhaust the gas of a callback
+const MAX_PRUNE_PER_RECORD: u64 = 5;
+
+// The summary of one verification kept in the audit log
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct AuditEntry {
+    pub index: u64,
+    pub id: Option<u64>,
+    /// hash of the copy of the leading group
+    pub message_hash: Option<String>,
+    pub outcome: VerificationOutcome,
+    pub threshold: u32,
+    /// weight of the leading group, 0 if no copy was aggregated
+    pub credibility_weight: u32,
+    pub total_credibility: u64,
+    pub groups: u32,
+    pub excluded: Vec<ExcludedSubmission>,
+    pub block_height: u64,
+    /// credibility of the validators used by the verification
+    pub snapshot: Vec<NodeCredibility>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AuditLog {
+    entries: LookupMap<u64, AuditEntry>,
+    by_message_hash: LookupMap<String, Vec<u64>>,
+    by_validator: LookupMap<PublicKey, Vec<u64>>,
+    // entries in [start, next) are kept
+    start: u64,
+    next: u64,
+    retention: u64,
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self {
+            entries: LookupMap::new(b'a'),
+            by_message_hash: LookupMap::new(b'i'),
+            by_validator: LookupMap::new(b'w'),
+            start: 0,
+            next: 0,
+            retention: DEFAULT_AUDIT_RETENTION,
+        }
+    }
+
+    fn record(&mut self, id: Option<u64>, report: &VerificationReport) {
+        let entry = AuditEntry {
+            index: self.next,
+            id,
+            message_hash: report.message_hash.clone(),
+            outcome: report.outcome.clone(),
+            threshold: report.threshold,
+            credibility_weight: report
+                .groups
+                .first()
+                .map_or(0, |group| group.credibility_weight),
+            total_credibility: report.total_credibility,
+            groups: report.groups.len() as u32,
+            excluded: report.excluded.clone(),
+            block_height: env::block_height(),
+            snapshot: report.snapshot.clone(),
+        };
+        if let Some(message_hash) = entry.message_hash.as_ref() {
+            Self::push_index(&mut self.by_message_hash, message_hash, entry.index);
+        }
+        for validator in Self::validators(&entry) {
+            Self::push_index(&mut self.by_validator, &validator, entry.index);
+        }
+        self.entries.insert(&entry.index, &entry);
+        self.next += 1;
+        self.prune(MAX_PRUNE_PER_RECORD);
+    }
+
+    /// Remove at most `limit` of the oldest entries beyond the retention, return the number removed
+    fn prune(&mut self, limit: u64) -> u64 {
+        let mut pruned = 0;
+        while pruned < limit && self.next - self.start > self.retention {
+            if let Some(entry) = self.entries.remove(&self.start) {
+                if let Some(message_hash) = entry.message_hash.as_ref() {
+                    Self::remove_index(&mut self.by_message_hash, message_hash, entry.index);
+                }
+                for validator in Self::validators(&entry) {
+                    Self::remove_index(&mut self.by_validator, &validator, entry.index);
+                }
+            }
+            self.start += 1;
+            pruned += 1;
+        }
+        pruned
+    }
+
+    fn validators(entry: &AuditEntry) -> Vec<PublicKey> {
+        let mut validators: Vec<PublicKey> = entry
+            .snapshot
+            .iter()
+            .map(|vc| vc.validator.clone())
+            .collect();
+        for excluded in entry.excluded.iter() {
+            if !validators.contains(&excluded.validator) {
+                validators.push(excluded.validator.clone());
+            }
+        }
+        validators
+    }
+
+    fn push_index<K: BorshSerialize>(index: &mut LookupMap<K, Vec<u64>>, key: &K, value: u64) {
+        let mut values = index.get(key).unwrap_or_default();
+        values.push(value);
+        index.insert(key, &values);
+    }
+
+    fn remove_index<K: BorshSerialize>(index: &mut LookupMap<K, Vec<u64>>, key: &K, value: u64) {
+        let mut values = index.get(key).unwrap_or_default();
+        values.retain(|v| *v != value);
+        if values.is_empty() {
+            index.remove(key);
+        } else {
+            index.insert(key, &values);
+        }
+    }
+
+    /// The index of the first entry at or after `block_height`
+    fn lower_bound(&self, block_height: u64) -> u64 {
+        let (mut low, mut high) = (self.start, self.next);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.entries.get(&mid).unwrap().block_height < block_height {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// set the number of the latest verifications kept in the audit log
+    #[private]
+    pub fn set_audit_retention(&mut self, retention: u64) {
+        self.audit_log.retention = retention;
+    }
+
+    pub fn get_audit_retention(&self) -> u64 {
+        self.audit_log.retention
+    }
+
+    /// @notice Remove at most `limit` entries beyond the retention, anyone can call it.
+    ///
+    /// @return The number of entries removed.
+    pub fn prune_audit_log(&mut self, limit: u64) -> u64 {
+        self.audit_log.prune(limit)
+    }
+
+    pub fn get_audit_entry(&self, index: u64) -> Option<AuditEntry> {
+        self.audit_log.entries.get(&index)
+    }
+
+    pub fn get_audit_by_message_hash(&self, message_hash: String) -> Vec<AuditEntry> {
+        self.audit_log
+            .by_message_hash
+            .get(&message_hash)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|index| self.audit_log.entries.get(index))
+            .collect()
+    }
+
+    /// the verifications `validator` took part in, oldest first
+    pub fn get_audit_by_validator(
+        &self,
+        validator: PublicKey,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<AuditEntry> {
+        self.audit_log
+            .by_validator
+            .get(&validator)
+            .unwrap_or_default()
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|index| self.audit_log.entries.get(index))
+            .collect()
+    }
+
+    /// the verifications between `from_block` and `to_block` included, oldest first
+    pub fn get_audit_by_block_range(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        limit: u64,
+    ) -> Vec<AuditEntry> {
+        (self.audit_log.lower_bound(from_block)..self.audit_log.next)
+            .map(|index| self.audit_log.entries.get(&index).unwrap())
+            .take_while(|entry| entry.block_height <= to_block)
+            .take(limit as usize)
+            .collect()
+    }
+
+    pub(crate) fn record_audit(&mut self, id: Option<u64>, report: &VerificationReport) {
+        self.audit_log.record(id, report);
+    }
+}
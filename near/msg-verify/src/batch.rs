@@ -0,0 +1,124 @@
+use crate::*;
+
+// messages verified by one call at most, each one keeps gas aside for its delivery and its report
+const MAX_BATCH_SIZE: usize = 10;
+
+#[near_bindgen]
+impl Contract {
+    /// @notice Same as `msg_verify` for many messages, in one call to read and one call to update `node_evaluation contract`.
+    ///
+    /// @dev The credibility is read once for the validators of every message, each message is aggregated on its own.
+    /// The behavior of the validators in each verification is reported in one `update_nodes_batch`,
+    /// scored on its own as `msg_verify` would, so the penalties of one message never add up with another.
+    ///
+    /// @param batch The id of each message in `cross-chain protocol contract` with its copies, at most `MAX_BATCH_SIZE`.
+    /// @param percentage Same as `msg_verify`, applied to every message.
+    /// @param signatures Same as `msg_verify`, the signatures of the copies of each message in the order of `batch`.
+    ///
+    /// @return The result of each verification in the order of `batch`. A `Vec` will be empty if its verification failed.
+    pub fn msg_verify_batch(
+        &mut self,
+        batch: Vec<(u64, Vec<MessageVerify>)>,
+        percentage: Option<u32>,
+        signatures: Option<Vec<Vec<Base64VecU8>>>,
+    ) -> PromiseOrValue<Vec<Vec<Message>>> {
+        require!(!batch.is_empty(), "empty batch");
+        require!(batch.len() <= MAX_BATCH_SIZE, "batch too large");
+        let signatures: Vec<Option<Vec<Base64VecU8>>> = match signatures {
+            Some(signatures) => {
+                require!(
+                    signatures.len() == batch.len(),
+                    "signatures required for each message"
+                );
+                signatures.into_iter().map(Some).collect()
+            }
+            None => vec![None; batch.len()],
+        };
+        let mut invalid_signatures: Vec<Option<Vec<PublicKey>>> = Vec::new();
+        let batch: Vec<(u64, Vec<MessageVerify>)> = batch
+            .into_iter()
+            .zip(signatures)
+            .map(|((id, msgs), signatures)| {
+                let (msgs, invalid) =
+                    self.check_submissions(msgs, percentage, Some(id), signatures);
+                invalid_signatures.push(invalid);
+                (id, msgs)
+            })
+            .collect();
+        if let Some(evaluation) = self.local_evaluation(&Self::validators_of_batch(&batch)) {
+            return PromiseOrValue::Value(self.verify_batch(
+                &evaluation,
+                batch,
+                percentage,
+                invalid_signatures,
+            ));
+        }
+        self.request_evaluation(Self::validators_of_batch(&batch))
+            .then(ext_self::batch_callback(
+                batch,
+                percentage,
+                invalid_signatures,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Self::evaluation_callback_gas(),
+            ))
+            .into()
+    }
+
+    #[private]
+    pub fn batch_callback(
+        &mut self,
+        batch: Vec<(u64, Vec<MessageVerify>)>,
+        percentage: Option<u32>,
+        invalid_signatures: Vec<Option<Vec<PublicKey>>>,
+    ) -> Vec<Vec<Message>> {
+        let evaluation = self.read_evaluation(&Self::validators_of_batch(&batch));
+        self.verify_batch(&evaluation, batch, percentage, invalid_signatures)
+    }
+}
+
+impl Contract {
+    fn verify_batch(
+        &mut self,
+        evaluation: &Evaluation,
+        batch: Vec<(u64, Vec<MessageVerify>)>,
+        percentage: Option<u32>,
+        invalid_signatures: Vec<Option<Vec<PublicKey>>>,
+    ) -> Vec<Vec<Message>> {
+        let reserved = Gas(self.delivery_gas().0 * batch.len() as u64);
+        let mut scorings: Vec<Scoring> = Vec::new();
+        let mut results: Vec<Vec<Message>> = Vec::new();
+        for ((id, msgs), invalid_signatures) in batch.into_iter().zip(invalid_signatures) {
+            let (report, mut scoring) = self.aggregate_copies(
+                evaluation,
+                msgs,
+                percentage,
+                Some(id),
+                invalid_signatures,
+                Absentees::Reported,
+            );
+            self.keep_verified(Some(id), &report, &mut scoring);
+            self.record_audit(Some(id), &report);
+            self.deliver(Some(id), &report);
+            scorings.push(scoring);
+            results.push(report.into_messages());
+        }
+        if evaluation.credibility.is_some() {
+            self.send_scorings(scorings, reserved);
+        }
+        results
+    }
+
+    /// The validators of every message of `batch`, each one once
+    fn validators_of_batch(batch: &[(u64, Vec<MessageVerify>)]) -> Vec<PublicKey> {
+        let mut keys: Vec<PublicKey> = Vec::new();
+        for (_, msgs) in batch.iter() {
+            for msg in msgs.iter() {
+                if !keys.contains(&msg.validator) {
+                    keys.push(msg.validator.clone());
+                }
+            }
+        }
+        keys
+    }
+}
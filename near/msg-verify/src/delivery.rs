@@ -0,0 +1,140 @@
+use crate::*;
+
+const GAS_FOR_DELIVERY: Gas = Gas(20_000_000_000_000);
+const GAS_FOR_DELIVERY_CALLBACK: Gas = Gas(10_000_000_000_000);
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DeliveryStatus {
+    /// the call to `cross-chain protocol contract` has not resolved yet
+    Pending,
+    Delivered,
+    /// the call failed, it can be retried with `redeliver`
+    Failed,
+}
+
+// The report of message `id` pushed to `cross-chain protocol contract`
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct Delivery {
+    pub id: u64,
+    pub report: VerificationReport,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// set the method of `cross-chain protocol contract` called with `(id, report)` after each verification, `None` to disable
+    #[private]
+    pub fn set_delivery_method(&mut self, method: Option<String>) {
+        self.delivery_method = method;
+    }
+
+    pub fn get_delivery_method(&self) -> Option<String> {
+        self.delivery_method.clone()
+    }
+
+    pub fn get_delivery(&self, id: u64) -> Option<Delivery> {
+        self.deliveries.get(&id)
+    }
+
+    /// @notice Push the report of message `id` again after its delivery failed, anyone can call it.
+    pub fn redeliver(&mut self, id: u64) -> Promise {
+        let delivery = self
+            .deliveries
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("delivery not found"));
+        require!(
+            delivery.status == DeliveryStatus::Failed,
+            "delivery not failed"
+        );
+        self.push_delivery(delivery)
+            .unwrap_or_else(|| env::panic_str("delivery disabled"))
+    }
+
+    #[private]
+    pub fn delivery_callback(&mut self, id: u64) -> bool {
+        require!(env::promise_results_count() == 1);
+        let mut delivery = self.deliveries.get(&id).unwrap();
+        let delivered = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        if delivered {
+            delivery.status = DeliveryStatus::Delivered;
+        } else {
+            delivery.status = DeliveryStatus::Failed;
+            emit_event(
+                "delivery_failed",
+                json!([{ "id": id, "attempts": delivery.attempts }]),
+            );
+        }
+        self.deliveries.insert(&id, &delivery);
+        delivered
+    }
+}
+
+impl Contract {
+    /// Push the report of message `id` to `cross-chain protocol contract` if the delivery is enabled.
+    /// A message verified again replaces its previous delivery.
+    pub(crate) fn deliver(&mut self, id: Option<u64>, report: &VerificationReport) {
+        if let Some(id) = id {
+            if self.delivery_method.is_none() {
+                return;
+            }
+            self.expire_deliveries();
+            if self.deliveries.get(&id).is_none() {
+                self.delivery_expiry.push(id);
+            }
+            self.push_delivery(Delivery {
+                id,
+                report: report.clone(),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+            });
+        }
+    }
+
+    /// The gas kept aside by a verification for its delivery
+    pub(crate) fn delivery_gas(&self) -> Gas {
+        match self.delivery_method {
+            Some(_) => GAS_FOR_DELIVERY + GAS_FOR_DELIVERY_CALLBACK,
+            None => Gas(0),
+        }
+    }
+
+    /// Remove some of the delivered reports older than `challenge_window` blocks, failed ones are kept for `redeliver`
+    fn expire_deliveries(&mut self) {
+        let now = env::block_height();
+        let expired = self.delivery_expiry.pop_expired(
+            now.saturating_sub(self.challenge_window),
+            MAX_EXPIRE_PER_INSERT,
+        );
+        for id in expired {
+            match self.deliveries.get(&id) {
+                Some(delivery) if delivery.status == DeliveryStatus::Delivered => {
+                    self.deliveries.remove(&id);
+                }
+                Some(_) => self.delivery_expiry.push(id),
+                None => {}
+            }
+        }
+    }
+
+    fn push_delivery(&mut self, mut delivery: Delivery) -> Option<Promise> {
+        let method = self.delivery_method.clone()?;
+        delivery.status = DeliveryStatus::Pending;
+        delivery.attempts += 1;
+        let id = delivery.id;
+        let args = json!({ "id": id, "report": delivery.report }).to_string();
+        self.deliveries.insert(&id, &delivery);
+        Some(
+            Promise::new(self.cross_contract_id.clone())
+                .function_call(method, args.into_bytes(), NO_DEPOSIT, GAS_FOR_DELIVERY)
+                .then(ext_self::delivery_callback(
+                    id,
+                    env::current_account_id(),
+                    NO_DEPOSIT,
+                    GAS_FOR_DELIVERY_CALLBACK,
+                )),
+        )
+    }
+}
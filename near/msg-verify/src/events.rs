@@ -0,0 +1,18 @@
+use near_sdk::env;
+use near_sdk::serde_json::{json, Value};
+
+const EVENT_STANDARD: &str = "msg-verify";
+const EVENT_VERSION: &str = "1.0.0";
+
+/// Log an event in the NEP-297 format, `EVENT_JSON:{"standard", "version", "event", "data"}`
+pub(crate) fn emit_event(event: &str, data: Value) {
+    env::log_str(&format!(
+        "EVENT_JSON:{}",
+        json!({
+            "standard": EVENT_STANDARD,
+            "version": EVENT_VERSION,
+            "event": event,
+            "data": data,
+        })
+    ));
+}
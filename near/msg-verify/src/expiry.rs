@@ -0,0 +1,44 @@
+use crate::*;
+
+// keys expired by one insertion at most, so a backlog does not exhaust the gas of a callback
+pub(crate) const MAX_EXPIRE_PER_INSERT: u64 = 5;
+
+// Keys of a map in the order they were inserted, so the map can be pruned from the oldest one
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ExpiryQueue<K> {
+    /// the key and the block height it was inserted at
+    keys: LookupMap<u64, (K, u64)>,
+    // keys in [start, next) are queued
+    start: u64,
+    next: u64,
+}
+
+impl<K: BorshSerialize + BorshDeserialize> ExpiryQueue<K> {
+    pub fn new(prefix: u8) -> Self {
+        Self {
+            keys: LookupMap::new(prefix),
+            start: 0,
+            next: 0,
+        }
+    }
+
+    pub fn push(&mut self, key: K) {
+        self.keys.insert(&self.next, &(key, env::block_height()));
+        self.next += 1;
+    }
+
+    /// Remove at most `limit` of the oldest keys inserted before `block_height`, the caller removes them from its map
+    pub fn pop_expired(&mut self, block_height: u64, limit: u64) -> Vec<K> {
+        let mut expired: Vec<K> = Vec::new();
+        while (expired.len() as u64) < limit && self.start < self.next {
+            let (key, inserted) = self.keys.get(&self.start).unwrap();
+            if inserted >= block_height {
+                break;
+            }
+            self.keys.remove(&self.start);
+            self.start += 1;
+            expired.push(key);
+        }
+        expired
+    }
+}
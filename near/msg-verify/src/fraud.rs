@@ -0,0 +1,233 @@
+use crate::*;
+use near_sdk::PromiseOrValue;
+
+const GAS_FOR_VERIFY_FRAUD_PROOF: Gas = Gas(20_000_000_000_000);
+const GAS_FOR_FRAUD_PROOF_CALLBACK: Gas = Gas(40_000_000_000_000);
+
+// A copy of a message signed by a validator, signature over `attestation_payload(id, message)`
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct Attestation {
+    pub validator: PublicKey,
+    pub message: Message,
+    pub signature: Base64VecU8,
+}
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum FraudEvidence {
+    /// copies of the same message id signed by validators of the trusted group, which differ from the verified message
+    ConflictingAttestations(Vec<Attestation>),
+    /// proof that the message never happened on the source chain, checked by the `fraud_verifier` contract
+    SourceChainProof(Base64VecU8),
+}
+
+// A verified message which can be challenged until `block_height + challenge_window`
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct VerifiedRecord {
+    pub id: Option<u64>,
+    pub message: Message,
+    /// the credibility weight threshold resolved for the verification
+    pub threshold: u32,
+    /// validators of the trusted group with their credibility before the update, but the ones already slashed
+    pub trusted: Vec<NodeCredibility>,
+    /// the copies of the trusted group were signed by their validators, unsigned copies may be attributed to anyone
+    pub authenticated: bool,
+    pub block_height: u64,
+    /// the message is proven fraudulent on the source chain, the whole trusted group was slashed
+    pub challenged: bool,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// set the number of blocks after verification during which a fraud proof is accepted
+    #[private]
+    pub fn set_challenge_window(&mut self, window: u64) {
+        self.challenge_window = window;
+    }
+
+    /// set the contract checking `FraudEvidence::SourceChainProof`
+    #[private]
+    pub fn set_fraud_verifier(&mut self, verifier: AccountId) {
+        self.fraud_verifier = Some(verifier);
+    }
+
+    pub fn get_verified_record(&self, message_hash: String) -> Option<VerifiedRecord> {
+        self.verified_messages.get(&message_hash)
+    }
+
+    /// @notice Called from anyone holding evidence that a verified message is fraudulent.
+    ///
+    /// @dev Within the challenge window, reverse the reward of the validators at fault, slash them,
+    /// and reward the challenger identified by `env::signer_account_pk()`.
+    /// Conflicting attestations put at fault the members of the trusted group who signed them,
+    /// and are only accepted if the copies of the trusted group were authenticated.
+    /// A source-chain proof puts at fault the whole trusted group.
+    ///
+    /// @param message_hash The hash of the verified message.
+    /// @param evidence Conflicting attestations are checked here, source-chain proofs by the `fraud_verifier` contract.
+    ///
+    /// @return True if the fraud is proven.
+    pub fn submit_fraud_proof(
+        &mut self,
+        message_hash: String,
+        evidence: FraudEvidence,
+    ) -> PromiseOrValue<bool> {
+        let record = self.challengeable_record(&message_hash);
+        let challenger = env::signer_account_pk();
+        match evidence {
+            FraudEvidence::ConflictingAttestations(attestations) => {
+                let id = record
+                    .id
+                    .unwrap_or_else(|| env::panic_str("message id unknown, attestation rejected"));
+                // an unsigned copy may have been attributed to a validator who signed the real message
+                require!(
+                    record.authenticated,
+                    "copies not authenticated, attestation rejected"
+                );
+                let record_id = record.id;
+                let offenders: Vec<NodeCredibility> = record
+                    .trusted
+                    .into_iter()
+                    .filter(|vc| {
+                        attestations.iter().any(|attestation| {
+                            vc.validator == attestation.validator
+                                && attestation.message.to_hash(record_id) != message_hash
+                                && signature::verify_ed25519(
+                                    &attestation.validator,
+                                    &attestation_payload(id, &attestation.message),
+                                    &attestation.signature.0,
+                                )
+                        })
+                    })
+                    .collect();
+                require!(!offenders.is_empty(), "no valid conflicting attestation");
+                self.punish_validators(&message_hash, offenders, challenger);
+                PromiseOrValue::Value(true)
+            }
+            FraudEvidence::SourceChainProof(proof) => {
+                let verifier = self
+                    .fraud_verifier
+                    .clone()
+                    .unwrap_or_else(|| env::panic_str("fraud verifier not set"));
+                ext_fv::verify_fraud_proof(
+                    record.message,
+                    proof,
+                    verifier,
+                    NO_DEPOSIT,
+                    GAS_FOR_VERIFY_FRAUD_PROOF,
+                )
+                .then(ext_self::fraud_proof_callback(
+                    message_hash,
+                    challenger,
+                    env::current_account_id(),
+                    NO_DEPOSIT,
+                    GAS_FOR_FRAUD_PROOF_CALLBACK,
+                ))
+                .into()
+            }
+        }
+    }
+
+    #[private]
+    pub fn fraud_proof_callback(&mut self, message_hash: String, challenger: PublicKey) -> bool {
+        require!(env::promise_results_count() == 1);
+        let proven = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                near_sdk::serde_json::from_slice::<bool>(&result).unwrap_or(false)
+            }
+            _ => false,
+        };
+        if proven {
+            let record = self.challengeable_record(&message_hash);
+            self.punish_validators(&message_hash, record.trusted, challenger);
+            let mut record = self.verified_messages.get(&message_hash).unwrap();
+            record.challenged = true;
+            self.verified_messages.insert(&message_hash, &record);
+        }
+        proven
+    }
+
+    /// Keep the verified message and the credibility of its trusted group until the challenge window closes
+    pub(crate) fn record_verified(
+        &mut self,
+        id: Option<u64>,
+        message: Message,
+        threshold: u32,
+        trusted: Vec<NodeCredibility>,
+        authenticated: bool,
+    ) {
+        self.expire_verified();
+        let message_hash = message.to_hash(id);
+        self.verified_expiry.push(message_hash.clone());
+        self.verified_messages.insert(
+            &message_hash,
+            &VerifiedRecord {
+                id,
+                message,
+                threshold,
+                trusted,
+                authenticated,
+                block_height: env::block_height(),
+                challenged: false,
+            },
+        );
+    }
+
+    fn challengeable_record(&self, message_hash: &String) -> VerifiedRecord {
+        let record = self
+            .verified_messages
+            .get(message_hash)
+            .unwrap_or_else(|| env::panic_str("verified message not found"));
+        require!(!record.challenged, "already challenged");
+        require!(!record.trusted.is_empty(), "trusted group already slashed");
+        require!(
+            env::block_height() <= record.block_height + self.challenge_window,
+            "challenge window closed"
+        );
+        record
+    }
+
+    /// Slash `offenders` of the trusted group of a verified message, they can not be slashed again for it
+    fn punish_validators(
+        &mut self,
+        message_hash: &String,
+        offenders: Vec<NodeCredibility>,
+        challenger: PublicKey,
+    ) {
+        let mut record = self.verified_messages.get(message_hash).unwrap();
+        record.trusted.retain(|vc| {
+            !offenders
+                .iter()
+                .any(|offender| offender.validator == vc.validator)
+        });
+        self.verified_messages.insert(message_hash, &record);
+        emit_event(
+            "fraud_proven",
+            json!([{
+                "message_hash": message_hash,
+                "trusted": offenders.iter().map(|vc| vc.validator.clone()).collect::<Vec<PublicKey>>(),
+                "challenger": challenger,
+            }]),
+        );
+        self.report_fraud(offenders, challenger);
+    }
+
+    /// Remove some of the verified messages whose challenge window closed
+    fn expire_verified(&mut self) {
+        let now = env::block_height();
+        let expired = self.verified_expiry.pop_expired(
+            now.saturating_sub(self.challenge_window),
+            MAX_EXPIRE_PER_INSERT,
+        );
+        for message_hash in expired {
+            // a message verified again is kept for its own window
+            if let Some(record) = self.verified_messages.get(&message_hash) {
+                if record.block_height + self.challenge_window < now {
+                    self.verified_messages.remove(&message_hash);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use cross_chain::Message;
+use near_sdk::borsh::BorshSerialize;
+use near_sdk::PublicKey;
+use sha2::{Digest, Sha256};
+
+/// Version of the canonical encoding, the first byte of the hashed bytes
+pub const MESSAGE_HASH_VERSION: u8 = 1;
+
+/// Canonical encoding of message `id`, Borsh of the version, the id and every field of `message` in this order:
+/// `from_chain`, `to_chain`, `sender`, `signer`, `sqos.reveal`, `content.contract`, `content.action`, `content.data`.
+/// Strings are prefixed with their length, so no two messages share an encoding.
+pub fn canonical_encoding(id: Option<u64>, message: &Message) -> Vec<u8> {
+    (
+        MESSAGE_HASH_VERSION,
+        id,
+        &message.from_chain,
+        &message.to_chain,
+        &message.sender,
+        &message.signer,
+        message.sqos.reveal,
+        &message.content.contract,
+        &message.content.action,
+        &message.content.data,
+    )
+        .try_to_vec()
+        .unwrap()
+}
+
+/// sha256 of `canonical_encoding(id, message)`, computed the same way by validators off-chain
+pub fn message_hash(id: Option<u64>, message: &Message) -> [u8; 32] {
+    Sha256::digest(&canonical_encoding(id, message)).into()
+}
+
+/// sha256 of `canonical_encoding(Some(id), message)`, the bytes of `validator` and `salt` in this order,
+/// committed by `validator` before revealing its copy of message `id`.
+/// The validator is hashed so a commitment copied from another validator can never be revealed.
+pub fn commitment(id: u64, message: &Message, validator: &PublicKey, salt: &[u8]) -> [u8; 32] {
+    let mut data = canonical_encoding(Some(id), message);
+    data.extend(validator.as_bytes());
+    data.extend(salt);
+    Sha256::digest(&data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cross_chain::{Content, SQOS};
+    use std::convert::TryFrom;
+
+    fn message() -> Message {
+        Message {
+            from_chain: "OTHER_CHAIN".to_string(),
+            to_chain: "NEAR_CHAIN".to_string(),
+            sender: "OTHER_CHAIN_LOCKER".to_string(),
+            signer: "OTHER_CHAIN_CALLER".to_string(),
+            sqos: SQOS { reveal: false },
+            content: Content {
+                contract: "ft.shanks.testnet".to_string(),
+                action: "ft_balance_of".to_string(),
+                data: "{\"account_id\": \"shanks.testnet\"}".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_shifted_boundaries() {
+        let mut a = message();
+        a.from_chain = "ab".to_string();
+        a.to_chain = "c".to_string();
+        let mut b = message();
+        b.from_chain = "a".to_string();
+        b.to_chain = "bc".to_string();
+        assert_ne!(message_hash(Some(1), &a), message_hash(Some(1), &b));
+
+        let mut a = message();
+        a.content.action = "ft_balance_of".to_string();
+        a.content.contract = "ft.shanks.testnet".to_string();
+        let mut b = message();
+        b.content.action = "ft_balance_offt.shanks".to_string();
+        b.content.contract = ".testnet".to_string();
+        assert_ne!(message_hash(Some(1), &a), message_hash(Some(1), &b));
+
+        let mut a = message();
+        a.content.data = "".to_string();
+        a.sender = "x".to_string();
+        let mut b = message();
+        b.content.data = "x".to_string();
+        b.sender = "".to_string();
+        assert_ne!(message_hash(Some(1), &a), message_hash(Some(1), &b));
+    }
+
+    #[test]
+    fn test_every_field_is_hashed() {
+        let hash = message_hash(Some(1), &message());
+        let mut other = message();
+        other.signer = "ANOTHER_CALLER".to_string();
+        assert_ne!(hash, message_hash(Some(1), &other));
+        let mut other = message();
+        other.sqos.reveal = true;
+        assert_ne!(hash, message_hash(Some(1), &other));
+        assert_ne!(hash, message_hash(Some(2), &message()));
+        assert_ne!(hash, message_hash(None, &message()));
+        assert_eq!(hash, message_hash(Some(1), &message()));
+    }
+
+    #[test]
+    fn test_canonical_encoding() {
+        let mut message = message();
+        message.from_chain = "A".to_string();
+        message.to_chain = "B".to_string();
+        message.sender = "".to_string();
+        message.signer = "".to_string();
+        message.content.contract = "".to_string();
+        message.content.action = "".to_string();
+        message.content.data = "".to_string();
+        let mut expected = vec![MESSAGE_HASH_VERSION, 1, 7, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend([1, 0, 0, 0, b'A', 1, 0, 0, 0, b'B']);
+        expected.extend([0, 0, 0, 0, 0, 0, 0, 0]);
+        expected.push(0);
+        expected.extend([0; 12]);
+        assert_eq!(expected, canonical_encoding(Some(7), &message));
+    }
+
+    #[test]
+    fn test_commitment() {
+        let validator = |seed: u8| {
+            let mut data = vec![0u8];
+            data.extend([seed; 32]);
+            PublicKey::try_from(data).unwrap()
+        };
+        let hash = commitment(1, &message(), &validator(1), b"salt");
+        assert_eq!(hash, commitment(1, &message(), &validator(1), b"salt"));
+        assert_ne!(hash, commitment(1, &message(), &validator(2), b"salt"));
+        assert_ne!(hash, commitment(1, &message(), &validator(1), b"pepper"));
+        assert_ne!(hash, commitment(2, &message(), &validator(1), b"salt"));
+        let mut other = message();
+        other.content.data = "".to_string();
+        assert_ne!(hash, commitment(1, &other, &validator(1), b"salt"));
+    }
+}
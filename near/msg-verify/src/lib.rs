@@ -1,55 +1,203 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LazyOption, LookupMap, UnorderedMap};
+use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::serde_json::json;
 use near_sdk::{
     env, ext_contract, log, near_bindgen, require, AccountId, Balance, Gas, PanicOnDefault,
-    Promise, PromiseResult, PublicKey,
+    Promise, PromiseOrValue, PromiseResult, PublicKey,
 };
 
 use std::collections::HashMap;
-use std::convert::TryFrom;
 // extern crate cross_chain;
 // extern crate node_evaluation;
 
 use cross_chain::{Message, MessageVerify};
-use node_evaluation::NodeCredibility;
+use node_evaluation::{
+    credibility_weight, BlsCommittee, NodeBehavior, NodeCredibility, ValidatorSet,
+};
+
+mod aggregate_signature;
+mod audit;
+mod batch;
+mod delivery;
+mod events;
+mod expiry;
+mod fraud;
+mod hash;
+mod optimistic;
+mod policy;
+mod report;
+mod session;
+mod signature;
+#[cfg(feature = "single-contract")]
+mod single;
+mod snapshot;
+mod voting;
+pub use audit::AuditEntry;
+use audit::AuditLog;
+pub use delivery::{Delivery, DeliveryStatus};
+use events::emit_event;
+use expiry::{ExpiryQueue, MAX_EXPIRE_PER_INSERT};
+pub use fraud::{Attestation, FraudEvidence, VerifiedRecord};
+pub use hash::{canonical_encoding, commitment, message_hash, MESSAGE_HASH_VERSION};
+pub use optimistic::{Bond, ClaimStatus, OptimisticClaim};
+use policy::distinct_operators;
+pub use policy::{Route, VerificationPolicy};
+pub use report::{
+    ExcludedSubmission, ExclusionReason, GroupReport, VerificationOutcome, VerificationReport,
+};
+pub use session::{Commitment, Session, SessionStatus};
+pub use snapshot::CredibilitySnapshot;
+pub use voting::{tally, GroupTally, Tally, Vote, VotingPower};
 
 const GAS_FOR_MSG_VERIFY: Gas = Gas(30_000_000_000_000);
 const GAS_FOR_GET_NODES: Gas = Gas(20_000_000_000_000);
+const GAS_FOR_GET_VALIDATOR_SET: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_GET_NODES_OPERATOR: Gas = Gas(10_000_000_000_000);
+#[cfg(not(feature = "single-contract"))]
 const GAS_FOR_CREDIBILITY_CALLBACK: Gas = Gas(30_000_000_000_000);
+const GAS_FOR_UPDATE_NODES: Gas = Gas(20_000_000_000_000);
+#[cfg(not(feature = "single-contract"))]
+const GAS_FOR_PUNISH_FRAUD: Gas = Gas(20_000_000_000_000);
 const NO_DEPOSIT: Balance = 0;
+const DEFAULT_CHALLENGE_WINDOW: u64 = 86_400;
+const DEFAULT_OPTIMISTIC_BOND: Balance = 1_000_000_000_000_000_000_000_000;
+// the weight of the leading group must be a strict majority of the selected validators when falling back to one vote per validator
+const BOOTSTRAP_MAJORITY: u32 = 5001;
 
 pub trait MsgVerify {
     /// @notice Verify cross-chain message from multi-copies committed by multi-nodes.
     /// The message is valid unless there are at least `requires` copies being the same.
     ///
-    /// @dev Cross contract call to get the credibility of the validators from `node_evaluation contract`.
+    /// @dev Cross contract call to get the credibility of the validators and the selected validator set of the current epoch from `node_evaluation contract`.
     /// Return to tell `cross-chain protocol contract` the result of the verification.
     /// Cross contract call to `node_evaluation contract` to update the credibility of the validators by their behavior.
+    /// Selected validators who did not submit any copy are reported as absent.
+    /// Validators who submitted different copies are excluded from the aggregation,
+    /// and reported as equivocators if the copies are signed.
+    /// Copies of validators outside the selected validator set, or not registered if no set is selected, are dropped and never scored.
+    /// The trusted group must also satisfy the quorum of the policy resolved for the message, see `resolve_policy`,
+    /// otherwise every group is reported as an exception like below the threshold.
+    /// So is every group if the weight of the second group is within the ambiguity margin of the policy, ties included.
+    /// If no copy has any credibility, the message is verified by a strict majority of the selected validators when `set_bootstrap_majority` is enabled.
+    /// The credibility is read from the snapshot pushed at the selection of the validator set while it is not stale,
+    /// without calling `node_evaluation contract`, see `set_snapshot_max_age`.
+    /// Built with the feature `single-contract`, the state of `node_evaluation` is part of this contract and is read and updated directly.
+    /// If a delivery method is set, the report of message `id` is also pushed to `cross-chain protocol contract`, see `set_delivery_method`.
     ///
     /// @param msgs The message copies to be verified.
+    /// @param id The id of the message in `cross-chain protocol contract`, required to challenge it with attestations.
+    /// @param signatures The ed25519 signature of each copy by its validator over `attestation_payload(id, message)`, in the order of `msgs`.
+    /// Copies with a bad signature are dropped and reported in an `invalid_signature` event.
+    /// Required unless disabled by `set_signature_required`, otherwise a copy may be attributed to a validator who never signed it.
     /// @param percentage [0~10000]. Example: 9558 means 95.58%. Minimum percent of weights for the identical copies.
     /// The percentage is the weighted sum of identical copies according to the credibility of the validators.
+    /// It can not be lower than the threshold of the policy resolved for the message, which applies if no percentage is given.
+    /// Neither can go below the contract-wide `credibility_weight_threshold`.
     ///
     /// @return The result of the verification. The `Vec` will be empty if failed.
-    fn msg_verify(&mut self, msgs: Vec<MessageVerify>) -> Promise;
+    fn msg_verify(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        signatures: Option<Vec<Base64VecU8>>,
+    ) -> PromiseOrValue<Vec<Message>>;
+
+    /// @notice Same as `msg_verify`, telling why the verification failed.
+    ///
+    /// @return The `VerificationReport` with the outcome, every group of identical copies and the excluded submissions.
+    fn msg_verify_report(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        signatures: Option<Vec<Base64VecU8>>,
+    ) -> PromiseOrValue<VerificationReport>;
 }
 
 #[ext_contract(ext_self)]
 pub trait ContractCallback {
-    fn credibility_callback(&mut self, msgs: Vec<MessageVerify>) -> Promise;
+    fn credibility_callback(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        invalid_signatures: Option<Vec<PublicKey>>,
+    ) -> Vec<Message>;
+
+    fn report_callback(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        invalid_signatures: Option<Vec<PublicKey>>,
+    ) -> VerificationReport;
+
+    fn fraud_proof_callback(&mut self, message_hash: String, challenger: PublicKey) -> bool;
+
+    fn challenge_callback(
+        &mut self,
+        id: u64,
+        msgs: Vec<MessageVerify>,
+        invalid_signatures: Vec<PublicKey>,
+    ) -> VerificationReport;
+
+    fn challenge_resolved_callback(&mut self, id: u64) -> Option<VerificationReport>;
+
+    fn claim_callback(&mut self, id: u64, validator: PublicKey, message: Message) -> bool;
+
+    fn batch_callback(
+        &mut self,
+        batch: Vec<(u64, Vec<MessageVerify>)>,
+        percentage: Option<u32>,
+        invalid_signatures: Vec<Option<Vec<PublicKey>>>,
+    ) -> Vec<Vec<Message>>;
+
+    fn delivery_callback(&mut self, id: u64) -> bool;
+
+    fn commit_callback(&mut self, id: u64, commitment: Commitment);
+
+    fn submit_callback(&mut self, id: u64, copy: MessageVerify) -> VerificationReport;
+
+    fn session_callback(&mut self, id: u64, msgs: Vec<MessageVerify>) -> VerificationReport;
+
+    fn aggregate_callback(
+        &mut self,
+        message: Message,
+        percentage: Option<u32>,
+        id: u64,
+        epoch: u64,
+        signature: Base64VecU8,
+        signers: Base64VecU8,
+    ) -> Vec<Message>;
 
     fn result_callback(&mut self, msg: Vec<Message>) -> Vec<Message>;
 }
 
 #[ext_contract(ext_ec)]
 pub trait EvaluationContract {
-    fn get_nodes_credibility(&self, nodes: Vec<PublicKey>) -> Vec<PublicKey, u32>;
+    fn get_nodes_credibility(&self, nodes: Vec<PublicKey>) -> Vec<NodeCredibility>;
+    fn get_nodes_operator(&self, nodes: Vec<PublicKey>) -> Vec<Option<AccountId>>;
+    fn get_current_validator_set(&self) -> Option<ValidatorSet>;
+    fn get_current_bls_committee(&self) -> Option<BlsCommittee>;
     fn update_nodes(
         &mut self,
         trusted: Vec<PublicKey>,
         untrusted: Vec<PublicKey>,
         exeception: Vec<(Vec<PublicKey>, u32)>,
+        absent: Vec<PublicKey>,
+        equivocators: Vec<PublicKey>,
     );
+    fn update_nodes_batch(&mut self, behaviors: Vec<NodeBehavior>);
+    fn punish_fraud(&mut self, trusted: Vec<NodeCredibility>, challenger: PublicKey);
+}
+
+#[ext_contract(ext_fv)]
+pub trait FraudVerifier {
+    /// @notice Check a source-chain proof that `message` never happened.
+    fn verify_fraud_proof(&self, message: Message, proof: Base64VecU8) -> bool;
 }
 
 #[near_bindgen]
@@ -60,15 +208,95 @@ pub struct Contract {
     cross_contract_id: AccountId,
     credibility_weight_threshold: u32,
     // aggregation_message:
+    verified_messages: LookupMap<String, VerifiedRecord>,
+    /// hashes of `verified_messages`, removed once the challenge window closes
+    verified_expiry: ExpiryQueue<String>,
+    challenge_window: u64,
+    fraud_verifier: Option<AccountId>,
+    signature_required: bool,
+    policies: UnorderedMap<Route, VerificationPolicy>,
+    audit_log: AuditLog,
+    optimistic_claims: LookupMap<u64, OptimisticClaim>,
+    bonds: LookupMap<PublicKey, Bond>,
+    optimistic_bond: Balance,
+    sessions: LookupMap<u64, Session>,
+    /// ids of `sessions`, removed once finalized and past the challenge window
+    session_expiry: ExpiryQueue<u64>,
+    delivery_method: Option<String>,
+    deliveries: LookupMap<u64, Delivery>,
+    /// ids of `deliveries`, removed once delivered and past the challenge window
+    delivery_expiry: ExpiryQueue<u64>,
+    bootstrap_majority: bool,
+    credibility_snapshot: LazyOption<CredibilitySnapshot>,
+    snapshot_max_age: Option<u64>,
+    /// the state of `node_evaluation` built in this contract, set by `inite`
+    #[cfg(feature = "single-contract")]
+    evaluation: Option<node_evaluation::Contract>,
+}
+
+// The behavior of the validators in one verification, reported to `node_evaluation contract`
+#[derive(Default)]
+struct Scoring {
+    trusted: Vec<PublicKey>,
+    untrusted: Vec<PublicKey>,
+    exeception: Vec<(Vec<PublicKey>, u32)>,
+    absent: Vec<PublicKey>,
+    equivocators: Vec<PublicKey>,
+    /// credibility of `trusted` when the message was verified
+    trusted_credibility: Vec<NodeCredibility>,
+    /// the copies of `trusted` were signed by their validators
+    authenticated: bool,
+}
+
+impl Scoring {
+    fn is_empty(&self) -> bool {
+        self.trusted.is_empty()
+            && self.untrusted.is_empty()
+            && self.exeception.is_empty()
+            && self.absent.is_empty()
+            && self.equivocators.is_empty()
+    }
+
+    /// Validators whose credibility drops by this scoring, but the banned equivocators
+    #[cfg(not(feature = "single-contract"))]
+    fn penalized(&self) -> Vec<PublicKey> {
+        self.untrusted
+            .iter()
+            .chain(self.exeception.iter().flat_map(|(group, _)| group.iter()))
+            .chain(self.absent.iter())
+            .cloned()
+            .collect()
+    }
+
+    fn into_behavior(self) -> NodeBehavior {
+        NodeBehavior {
+            trusted: self.trusted,
+            untrusted: self.untrusted,
+            exeception: self.exeception,
+            absent: self.absent,
+            equivocators: self.equivocators,
+        }
+    }
+}
+
+// The results of `request_evaluation`
+struct Evaluation {
+    /// `None` if the credibility could not be read
+    credibility: Option<Vec<NodeCredibility>>,
+    validator_set: Option<ValidatorSet>,
+    /// `None` if the operators could not be read
+    operators: Option<HashMap<PublicKey, AccountId>>,
 }
 
-#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
-#[serde(tag = "type", crate = "near_sdk::serde")]
-pub struct GroupCredibility {
-    // message: Message,
-    group_credibility_value: u32,
-    credibility_weight: u32,
-    validators: Vec<PublicKey>,
+// How the selected validators without a copy take part in an aggregation
+#[derive(Clone, Copy, PartialEq)]
+enum Absentees {
+    /// left out, they may still submit
+    Ignored,
+    /// reported as absent to `node_evaluation contract`
+    Reported,
+    /// weighed as votes for no copy without being reported, for copies they are not expected to submit
+    Weighed,
 }
 
 #[near_bindgen]
@@ -80,103 +308,549 @@ impl Contract {
         node_eva_addr: AccountId,
         credibility_weight_threshold: u32,
     ) -> Self {
+        // the calls to `node_evaluation` left as promises come back to this contract
+        #[cfg(feature = "single-contract")]
+        require!(
+            node_eva_addr == env::current_account_id(),
+            "node_eva_addr must be this contract"
+        );
         Self {
             cross_contract_id,
             node_ev_address: node_eva_addr,
             credibility_weight_threshold: credibility_weight_threshold,
+            verified_messages: LookupMap::new(b'v'),
+            verified_expiry: ExpiryQueue::new(b'f'),
+            challenge_window: DEFAULT_CHALLENGE_WINDOW,
+            fraud_verifier: None,
+            signature_required: true,
+            policies: UnorderedMap::new(b'p'),
+            audit_log: AuditLog::new(),
+            optimistic_claims: LookupMap::new(b'c'),
+            bonds: LookupMap::new(b'd'),
+            optimistic_bond: DEFAULT_OPTIMISTIC_BOND,
+            sessions: LookupMap::new(b's'),
+            session_expiry: ExpiryQueue::new(b'g'),
+            delivery_method: None,
+            deliveries: LookupMap::new(b'r'),
+            delivery_expiry: ExpiryQueue::new(b'h'),
+            bootstrap_majority: false,
+            credibility_snapshot: LazyOption::new(b'e', None),
+            snapshot_max_age: None,
+            #[cfg(feature = "single-contract")]
+            evaluation: None,
         }
     }
 
+    /// The threshold of one verification, neither the caller nor the policy can go below `credibility_weight_threshold`
+    pub(crate) fn resolve_threshold(
+        &self,
+        percentage: Option<u32>,
+        policy: &VerificationPolicy,
+    ) -> u32 {
+        std::cmp::max(
+            percentage.unwrap_or(0),
+            std::cmp::max(
+                policy.credibility_weight_threshold,
+                self.credibility_weight_threshold,
+            ),
+        )
+    }
+
+    /// set whether every copy must carry the signature of its validator, required by default.
+    /// Disabling it is an explicit opt-out of the owner while `cross-chain protocol contract` submits unsigned copies,
+    /// any unsigned copy may then be attributed to a validator who never signed it.
+    #[private]
+    pub fn set_signature_required(&mut self, required: bool) {
+        self.signature_required = required;
+    }
+
+    pub fn get_signature_required(&self) -> bool {
+        self.signature_required
+    }
+
+    /// set whether copies without any credibility fall back to a majority of the selected validators, for a network bootstrap.
+    /// Validators without credibility gain none by being trusted, the bootstrap ends once the owner of `node_evaluation contract`
+    /// seeds their credibility with `update_storage_date`, or new nodes register with `set_initial_credibility` above 0.
     #[private]
-    pub fn credibility_callback(&self, msgs: Vec<MessageVerify>) -> Vec<Message> {
-        require!(env::promise_results_count() == 1);
-        let mut valid_message: Vec<Message> = Vec::new();
-        match env::promise_result(0) {
+    pub fn set_bootstrap_majority(&mut self, enabled: bool) {
+        self.bootstrap_majority = enabled;
+    }
+
+    pub fn get_bootstrap_majority(&self) -> bool {
+        self.bootstrap_majority
+    }
+
+    #[private]
+    pub fn credibility_callback(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        invalid_signatures: Option<Vec<PublicKey>>,
+    ) -> Vec<Message> {
+        let report = self.verify_copies(
+            msgs,
+            percentage,
+            id,
+            invalid_signatures,
+            Absentees::Reported,
+        );
+        self.record_audit(id, &report);
+        self.deliver(id, &report);
+        report.into_messages()
+    }
+
+    #[private]
+    pub fn report_callback(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        invalid_signatures: Option<Vec<PublicKey>>,
+    ) -> VerificationReport {
+        let report = self.verify_copies(
+            msgs,
+            percentage,
+            id,
+            invalid_signatures,
+            Absentees::Reported,
+        );
+        self.record_audit(id, &report);
+        self.deliver(id, &report);
+        report
+    }
+
+    /// Aggregate the copies by the credibility of their validators and report the behavior to `node_evaluation contract`.
+    /// Selected validators without a copy take part as told by `absentees`.
+    /// `invalid_signatures` is `None` if the copies are not authenticated by their validators.
+    fn verify_copies(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        invalid_signatures: Option<Vec<PublicKey>>,
+        absentees: Absentees,
+    ) -> VerificationReport {
+        let evaluation = self.read_evaluation(&Self::validators_of(&msgs));
+        self.score_copies(
+            &evaluation,
+            msgs,
+            percentage,
+            id,
+            invalid_signatures,
+            absentees,
+        )
+    }
+
+    /// Same as `verify_copies` by an evaluation at hand
+    fn score_copies(
+        &mut self,
+        evaluation: &Evaluation,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        invalid_signatures: Option<Vec<PublicKey>>,
+        absentees: Absentees,
+    ) -> VerificationReport {
+        let (report, scoring) = self.aggregate_copies(
+            evaluation,
+            msgs,
+            percentage,
+            id,
+            invalid_signatures,
+            absentees,
+        );
+        self.apply_scoring(id, &report, scoring);
+        report
+    }
+
+    /// Read the results of `request_evaluation(keys)`, which also refresh the snapshot
+    fn read_evaluation(&mut self, keys: &[PublicKey]) -> Evaluation {
+        require!(env::promise_results_count() == 3);
+        let credibility = match env::promise_result(0) {
             PromiseResult::Successful(result) => {
                 match near_sdk::serde_json::from_slice::<Vec<NodeCredibility>>(&result) {
-                    Ok(validators_credibility) => {
-                        // validate Messages
-                        let mut aggregation_result: HashMap<String, (Message, GroupCredibility)> =
-                            HashMap::new();
-                        let mut credibility_map: HashMap<PublicKey, u32> = HashMap::new();
-                        for vc in validators_credibility {
-                            credibility_map.insert(
-                                PublicKey::try_from(vc.validator).unwrap(),
-                                vc.credibility_value,
-                            );
-                        }
-                        let mut total_credibility = 0;
-                        for msg in msgs {
-                            let hash = msg.message.to_hash();
-                            let pk = msg.validator.clone();
-                            let credibility_value = credibility_map.get(&pk).unwrap_or(&0u32);
-                            let group_info = aggregation_result.entry(hash).or_insert((
-                                msg.message,
-                                GroupCredibility {
-                                    group_credibility_value: *credibility_value,
-                                    credibility_weight: 0,
-                                    validators: vec![pk.clone()],
-                                },
-                            ));
-                            if !(*group_info).1.validators.contains(&pk) {
-                                (*group_info).1.group_credibility_value += *credibility_value;
-                                (*group_info).1.validators.push(pk);
-                            }
-                            total_credibility += credibility_value;
-                        }
-
-                        let mut sort_vec: Vec<(Message, GroupCredibility)> = aggregation_result
-                            .iter()
-                            .map(|(_, value)| {
-                                let mut return_value = value.clone();
-                                return_value.1.credibility_weight =
-                                    value.1.group_credibility_value * 10000 / total_credibility;
-                                return_value
-                            })
-                            .collect();
-                        sort_vec
-                            .sort_by(|a, b| b.1.credibility_weight.cmp(&a.1.credibility_weight));
-                        // let mut node_behaviors: Vec<NodeBehavior> = Vec::new();
-                        let mut trusted: Vec<PublicKey> = Vec::new();
-                        let mut untrusted: Vec<PublicKey> = Vec::new();
-                        let mut exeception: Vec<(Vec<PublicKey>, u32)> = Vec::new();
-                        log!(
-                            "credibility_weight: {}, credibility_weight_threshold: {}",
-                            sort_vec[0].1.credibility_weight,
-                            self.credibility_weight_threshold
-                        );
-                        if sort_vec[0].1.credibility_weight >= self.credibility_weight_threshold {
-                            valid_message.push(sort_vec[0].0.clone());
-                            trusted = sort_vec.remove(0).1.validators;
-                            for group in sort_vec {
-                                untrusted.extend(group.1.validators);
-                            }
-                        } else {
-                            for group in sort_vec {
-                                exeception.push((group.1.validators, group.1.credibility_weight));
-                            }
-                        }
-                        // let promise = Promise::new(self.node_ev_address);
-                        ext_ec::update_nodes(
-                            trusted,
-                            untrusted,
-                            exeception,
-                            self.node_ev_address.clone(),
-                            NO_DEPOSIT,
-                            env::prepaid_gas() - GAS_FOR_CREDIBILITY_CALLBACK,
-                        );
-                    }
+                    Ok(validators_credibility) => Some(validators_credibility),
                     Err(err) => {
                         log!("resolve promise result failed, {}", err);
-                        env::panic_str("in callback!, `from_slice` error!");
+                        None
                     }
                 }
             }
             _ => {
-                env::panic_str("in callback!, but params error!");
+                log!("get nodes credibility failed");
+                None
+            }
+        };
+        let validator_set: Option<ValidatorSet> = match env::promise_result(1) {
+            PromiseResult::Successful(result) => {
+                near_sdk::serde_json::from_slice::<Option<ValidatorSet>>(&result)
+                    .unwrap_or_else(|_| env::panic_str("in callback!, `from_slice` error!"))
+            }
+            _ => {
+                log!("get current validator set failed, skip liveness check");
+                None
+            }
+        };
+        let operators: Option<HashMap<PublicKey, AccountId>> = match env::promise_result(2) {
+            PromiseResult::Successful(result) => Some(
+                near_sdk::serde_json::from_slice::<Vec<Option<AccountId>>>(&result)
+                    .unwrap_or_else(|_| env::panic_str("in callback!, `from_slice` error!"))
+                    .into_iter()
+                    .zip(keys.iter())
+                    .filter_map(|(operator, pk)| operator.map(|op| (pk.clone(), op)))
+                    .collect(),
+            ),
+            _ => {
+                log!("get nodes operator failed, no operator is counted");
+                None
+            }
+        };
+        let evaluation = Evaluation {
+            credibility,
+            validator_set,
+            operators,
+        };
+        #[cfg(not(feature = "single-contract"))]
+        self.refresh_snapshot(&evaluation);
+        evaluation
+    }
+
+    /// Aggregate the copies by the results of `request_evaluation` without changing any state.
+    fn aggregate_copies(
+        &self,
+        evaluation: &Evaluation,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        invalid_signatures: Option<Vec<PublicKey>>,
+        absentees: Absentees,
+    ) -> (VerificationReport, Scoring) {
+        // only validators who signed their copies can be found equivocating
+        let authenticated = invalid_signatures.is_some();
+        let invalid_signatures = invalid_signatures.unwrap_or_default();
+        let mut report = VerificationReport::new(VerificationOutcome::NoValidCopy);
+        report.exclude(&invalid_signatures, ExclusionReason::InvalidSignature);
+        // non-members are never sent to `node_evaluation contract`
+        let non_members = Self::non_members(evaluation, &msgs);
+        report.exclude(&non_members, ExclusionReason::NonMember);
+        let msgs: Vec<MessageVerify> = msgs
+            .into_iter()
+            .filter(|msg| !non_members.contains(&msg.validator))
+            .collect();
+        let missing: Vec<NodeCredibility> = match evaluation
+            .validator_set
+            .as_ref()
+            .filter(|_| absentees != Absentees::Ignored)
+        {
+            Some(validator_set) => validator_set
+                .validators
+                .iter()
+                .filter(|vc| !msgs.iter().any(|msg| msg.validator == vc.validator))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        let absent: Vec<PublicKey> = match absentees {
+            Absentees::Reported => missing.iter().map(|vc| vc.validator.clone()).collect(),
+            _ => Vec::new(),
+        };
+        if msgs.is_empty() && invalid_signatures.is_empty() && non_members.is_empty() {
+            report.outcome = VerificationOutcome::NoSubmissions;
+            return (
+                report,
+                Scoring {
+                    absent,
+                    ..Default::default()
+                },
+            );
+        }
+        let no_operators = HashMap::new();
+        let operators = evaluation.operators.as_ref().unwrap_or(&no_operators);
+        // the evaluation may cover the validators of other messages
+        let validators_credibility: Vec<NodeCredibility> = match evaluation.credibility.as_ref() {
+            Some(validators_credibility) => validators_credibility
+                .iter()
+                .filter(|vc| msgs.iter().any(|msg| msg.validator == vc.validator))
+                .cloned()
+                .collect(),
+            None => {
+                report.outcome = VerificationOutcome::EvaluationFailed;
+                return (report, Scoring::default());
+            }
+        };
+        let (msgs, equivocators) = Self::exclude_equivocators(msgs, id, authenticated);
+        report.exclude(&equivocators, ExclusionReason::Equivocation);
+        let equivocators = if authenticated {
+            equivocators
+        } else {
+            Vec::new()
+        };
+        report.snapshot = validators_credibility.clone();
+        // validate Messages
+        let credibility_map: HashMap<PublicKey, u32> = validators_credibility
+            .into_iter()
+            .map(|vc| (vc.validator, vc.credibility_value))
+            .collect();
+        // the voting power, the threshold and the quorum follow the policy of the leading copy,
+        // the copies are tallied with the policy of the first one to find it
+        let mut voting_power = msgs.first().map_or(VotingPower::Linear, |msg| {
+            self.resolve_policy(msg.message.clone()).voting_power
+        });
+        let mut messages: HashMap<String, Message> = HashMap::new();
+        let mut votes: Vec<Vote> = Vec::new();
+        for msg in msgs {
+            let message_hash = msg.message.to_hash(id);
+            votes.push(Vote {
+                message_hash: message_hash.clone(),
+                credibility: *credibility_map.get(&msg.validator).unwrap_or(&0u32),
+                stake: self.stake_of(&msg.validator, &voting_power),
+                operator: operators.get(&msg.validator).cloned(),
+                validator: msg.validator,
+            });
+            messages.entry(message_hash).or_insert(msg.message);
+        }
+        if absentees == Absentees::Weighed {
+            // they vote for no copy, so every group weighs against the whole validator set
+            for vc in missing {
+                votes.push(Vote {
+                    message_hash: String::new(),
+                    credibility: vc.credibility_value,
+                    stake: self.stake_of(&vc.validator, &voting_power),
+                    operator: operators.get(&vc.validator).cloned(),
+                    validator: vc.validator,
+                });
+            }
+        }
+        let mut tally = Self::tally_copies(&votes, &voting_power);
+        let leading_power = tally.groups.first().map(|group| {
+            self.resolve_policy(messages[&group.message_hash].clone())
+                .voting_power
+        });
+        if let Some(leading_power) = leading_power.filter(|power| *power != voting_power) {
+            voting_power = leading_power;
+            for vote in votes.iter_mut() {
+                vote.stake = self.stake_of(&vote.validator, &voting_power);
+            }
+            tally = Self::tally_copies(&votes, &voting_power);
+        }
+        // without any credibility, a fresh network can fall back to one vote per selected validator
+        let members = evaluation
+            .validator_set
+            .as_ref()
+            .map_or(0, |validator_set| validator_set.validators.len() as u64);
+        let bootstrap = !tally.groups.is_empty()
+            && tally.total_credibility == 0
+            && self.bootstrap_majority
+            && members > 0;
+        if bootstrap {
+            tally = Self::tally_copies(&votes, &VotingPower::Count);
+            // the majority is counted over the selected validators, those without a copy vote for none
+            tally.total_power = members as u128;
+            for group in tally.groups.iter_mut() {
+                group.weight = credibility_weight(group.power, members as u128);
+            }
+        }
+        report.total_credibility = tally.total_credibility;
+        report.total_power = U128(tally.total_power);
+        report.groups = tally
+            .groups
+            .iter()
+            .map(|group| GroupReport {
+                message_hash: group.message_hash.clone(),
+                validators: group.validators.clone(),
+                operators: distinct_operators(&group.validators, operators),
+                credibility: group.credibility,
+                power: U128(group.power),
+                credibility_weight: group.weight,
+            })
+            .collect();
+        // let mut node_behaviors: Vec<NodeBehavior> = Vec::new();
+        let mut scoring = Scoring {
+            absent,
+            equivocators,
+            ..Default::default()
+        };
+        if tally.groups.is_empty() {
+            log!("no copy left after excluding equivocators");
+        } else if tally.total_credibility == 0 && !bootstrap {
+            report.outcome = VerificationOutcome::NoCredibleSubmitters;
+            report.message_hash = Some(report.groups[0].message_hash.clone());
+            log!("no credibility in the copies");
+        } else {
+            let leading_weight = tally.groups[0].weight;
+            let message = messages.remove(&tally.groups[0].message_hash).unwrap();
+            let policy = self.resolve_policy(message.clone());
+            let mut threshold = self.resolve_threshold(percentage, &policy);
+            if bootstrap {
+                log!(
+                    "bootstrap majority, {} of {} validators",
+                    report.groups[0].validators.len(),
+                    members
+                );
+                threshold = std::cmp::max(threshold, BOOTSTRAP_MAJORITY);
+            }
+            report.threshold = threshold;
+            report.message_hash = Some(report.groups[0].message_hash.clone());
+            log!(
+                "credibility_weight: {}, credibility_weight_threshold: {}",
+                leading_weight,
+                threshold
+            );
+            if !bootstrap && policy.voting_power != voting_power {
+                // tallied again, the lead went to a copy whose route counts the votes another way
+                report.outcome = VerificationOutcome::Ambiguous;
+                log!("the routes of the leading copies disagree on the voting power");
+                for group in tally.groups {
+                    scoring.exeception.push((group.validators, group.weight));
+                }
+            } else if leading_weight < threshold {
+                report.outcome = VerificationOutcome::ThresholdNotMet;
+                for group in tally.groups {
+                    scoring.exeception.push((group.validators, group.weight));
+                }
+            } else if policy.is_ambiguous(&report.groups) {
+                report.outcome = VerificationOutcome::Ambiguous;
+                log!(
+                    "ambiguous groups, credibility_weight: {}, {}",
+                    leading_weight,
+                    report.groups[1].credibility_weight
+                );
+                for group in tally.groups {
+                    scoring.exeception.push((group.validators, group.weight));
+                }
+            } else if !policy.is_satisfied_by(&report.groups[0]) {
+                report.outcome = VerificationOutcome::QuorumNotMet;
+                log!(
+                    "quorum of the policy not reached, validators: {}, operators: {}, credibility: {}",
+                    report.groups[0].validators.len(),
+                    report.groups[0].operators,
+                    report.groups[0].credibility
+                );
+                for group in tally.groups {
+                    scoring.exeception.push((group.validators, group.weight));
+                }
+            } else {
+                report.outcome = VerificationOutcome::Verified;
+                report.message = Some(message);
+                let mut groups = tally.groups.into_iter();
+                let group = groups.next().unwrap();
+                scoring.trusted_credibility = group
+                    .validators
+                    .iter()
+                    .map(|pk| NodeCredibility {
+                        validator: pk.clone(),
+                        credibility_value: *credibility_map.get(pk).unwrap_or(&0u32),
+                    })
+                    .collect();
+                scoring.trusted = group.validators;
+                scoring.authenticated = authenticated;
+                for group in groups {
+                    scoring.untrusted.extend(group.validators);
+                }
             }
         }
-        return valid_message;
+        (report, scoring)
+    }
+
+    /// Same as `voting::tally`, the votes for no copy only weigh in the total power
+    fn tally_copies(votes: &[Vote], voting_power: &VotingPower) -> Tally {
+        let mut tally = voting::tally(votes, voting_power);
+        tally.groups.retain(|group| !group.message_hash.is_empty());
+        tally
+    }
+
+    /// Keep the verified message and report the behavior of the validators to `node_evaluation contract`.
+    fn apply_scoring(
+        &mut self,
+        id: Option<u64>,
+        report: &VerificationReport,
+        mut scoring: Scoring,
+    ) {
+        if report.outcome == VerificationOutcome::EvaluationFailed
+            || (report.outcome == VerificationOutcome::NoSubmissions && scoring.is_empty())
+        {
+            return;
+        }
+        self.keep_verified(id, report, &mut scoring);
+        self.send_scoring(scoring, self.delivery_gas());
+    }
+
+    fn keep_verified(
+        &mut self,
+        id: Option<u64>,
+        report: &VerificationReport,
+        scoring: &mut Scoring,
+    ) {
+        if let Some(message) = report.message.clone() {
+            let trusted = std::mem::take(&mut scoring.trusted_credibility);
+            self.record_verified(
+                id,
+                message,
+                report.threshold,
+                trusted,
+                scoring.authenticated,
+            );
+        }
+    }
+
+    /// Validators of `msgs` outside the selected validator set, or not registered if no validator set is selected.
+    /// Every validator is a member if neither could be read.
+    fn non_members(evaluation: &Evaluation, msgs: &[MessageVerify]) -> Vec<PublicKey> {
+        let mut non_members: Vec<PublicKey> = Vec::new();
+        for msg in msgs.iter() {
+            if !Self::is_member(evaluation, &msg.validator) && !non_members.contains(&msg.validator)
+            {
+                non_members.push(msg.validator.clone());
+            }
+        }
+        non_members
+    }
+
+    /// Whether `pk` is in the selected validator set, or registered if no validator set is selected, see `non_members`
+    fn is_member(evaluation: &Evaluation, pk: &PublicKey) -> bool {
+        match (&evaluation.validator_set, &evaluation.operators) {
+            (Some(validator_set), _) => validator_set.contains(pk),
+            (None, Some(operators)) => operators.contains_key(pk),
+            (None, None) => true,
+        }
+    }
+
+    /// Split out validators who submitted more than one distinct copy of the message.
+    /// Their copies are dropped from the aggregation.
+    /// An `equivocation` event is emitted for each of them if the copies are `authenticated` by their signatures,
+    /// unsigned copies may be forged so they prove nothing against the validator.
+    fn exclude_equivocators(
+        msgs: Vec<MessageVerify>,
+        id: Option<u64>,
+        authenticated: bool,
+    ) -> (Vec<MessageVerify>, Vec<PublicKey>) {
+        let mut signed_hashes: HashMap<PublicKey, Vec<String>> = HashMap::new();
+        for msg in msgs.iter() {
+            let hashes = signed_hashes.entry(msg.validator.clone()).or_default();
+            let hash = msg.message.to_hash(id);
+            if !hashes.contains(&hash) {
+                hashes.push(hash);
+            }
+        }
+        let mut equivocators: Vec<PublicKey> = Vec::new();
+        for msg in msgs.iter() {
+            let hashes = &signed_hashes[&msg.validator];
+            if hashes.len() > 1 && !equivocators.contains(&msg.validator) {
+                if authenticated {
+                    emit_event(
+                        "equivocation",
+                        json!([{ "validator": msg.validator, "hashes": hashes }]),
+                    );
+                }
+                equivocators.push(msg.validator.clone());
+            }
+        }
+        let msgs = msgs
+            .into_iter()
+            .filter(|msg| !equivocators.contains(&msg.validator))
+            .collect();
+        (msgs, equivocators)
     }
 
     #[private]
@@ -185,45 +859,246 @@ impl Contract {
     }
 }
 
+/// Bytes signed by a validator to attest `message` as the message `id`
+pub fn attestation_payload(id: u64, message: &Message) -> Vec<u8> {
+    message_hash(Some(id), message).to_vec()
+}
+
 pub trait ToHash {
-    fn to_hash(&self) -> String;
+    fn to_hash(&self, id: Option<u64>) -> String;
 }
 
 impl ToHash for Message {
-    fn to_hash(&self) -> String {
-        let mut s = self.from_chain.clone();
-        s += &self.to_chain.clone();
-        s += &self.sender.clone();
-        s += &self.content.action.clone();
-        s += &self.content.contract.clone();
-        s += &self.content.data.clone();
-
-        s = hex::encode(env::sha256(s.as_bytes()));
-        s
+    /// hex encoded `message_hash(id, self)`
+    fn to_hash(&self, id: Option<u64>) -> String {
+        hex::encode(message_hash(id, self))
     }
 }
 
-#[near_bindgen]
-impl MsgVerify for Contract {
-    fn msg_verify(&mut self, msgs: Vec<MessageVerify>) -> Promise {
+impl Contract {
+    /// Drop the copies with a bad signature, return the remaining copies and the validators of the dropped ones,
+    /// `None` if the copies are not signed.
+    fn check_submissions(
+        &self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        signatures: Option<Vec<Base64VecU8>>,
+    ) -> (Vec<MessageVerify>, Option<Vec<PublicKey>>) {
         assert_eq!(env::predecessor_account_id(), self.cross_contract_id);
+        require!(percentage.unwrap_or(0) <= 10000, "percentage out of range");
+        require!(
+            !msgs.iter().any(|msg| msg.message.sqos.reveal),
+            "message requires commit-reveal"
+        );
+        match signatures {
+            Some(signatures) => {
+                let id =
+                    id.unwrap_or_else(|| env::panic_str("message id required to check signatures"));
+                let (valid, invalid) = signature::verify_submissions(msgs, &signatures, id);
+                let invalid: Vec<PublicKey> =
+                    invalid.into_iter().map(|msg| msg.validator).collect();
+                if !invalid.is_empty() {
+                    emit_event(
+                        "invalid_signature",
+                        json!([{ "id": id, "validators": invalid }]),
+                    );
+                }
+                (valid, Some(invalid))
+            }
+            None => {
+                require!(!self.signature_required, "signatures required");
+                (msgs, None)
+            }
+        }
+    }
+
+    /// Verify the copies right away, `None` if the evaluation must be requested, see `local_evaluation`
+    fn verify_cached(
+        &mut self,
+        msgs: &[MessageVerify],
+        percentage: Option<u32>,
+        id: Option<u64>,
+        invalid_signatures: &Option<Vec<PublicKey>>,
+    ) -> Option<VerificationReport> {
+        let evaluation = self.local_evaluation(&Self::validators_of(msgs))?;
+        let report = self.score_copies(
+            &evaluation,
+            msgs.to_vec(),
+            percentage,
+            id,
+            invalid_signatures.clone(),
+            Absentees::Reported,
+        );
+        self.record_audit(id, &report);
+        self.deliver(id, &report);
+        Some(report)
+    }
+
+    fn validators_of(msgs: &[MessageVerify]) -> Vec<PublicKey> {
         let mut keys: Vec<PublicKey> = Vec::new();
         for value in msgs.iter() {
             keys.push(value.validator.clone());
             // keys.push(value.validator.into());
         }
+        keys
+    }
+
+    /// Get the credibility and the operators of the validators of `msgs` and the current validator set
+    fn get_evaluation(&self, msgs: &[MessageVerify]) -> Promise {
+        self.request_evaluation(Self::validators_of(msgs))
+    }
+
+    /// Get the credibility and the operators of `keys` and the current validator set
+    fn request_evaluation(&self, keys: Vec<PublicKey>) -> Promise {
         log!("msg_verify: {}", env::prepaid_gas().0);
         ext_ec::get_nodes_credibility(
-            keys,
+            keys.clone(),
             self.node_ev_address.clone(),
             NO_DEPOSIT,
             GAS_FOR_GET_NODES,
         )
-        .then(ext_self::credibility_callback(
-            msgs,
-            env::current_account_id(),
-            0,
-            env::prepaid_gas() - GAS_FOR_GET_NODES - GAS_FOR_MSG_VERIFY,
+        .and(ext_ec::get_current_validator_set(
+            self.node_ev_address.clone(),
+            NO_DEPOSIT,
+            GAS_FOR_GET_VALIDATOR_SET,
         ))
+        .and(ext_ec::get_nodes_operator(
+            keys,
+            self.node_ev_address.clone(),
+            NO_DEPOSIT,
+            GAS_FOR_GET_NODES_OPERATOR,
+        ))
+    }
+
+    /// The gas left to the callback of `request_evaluation`, saturating like `report_gas`
+    pub(crate) fn evaluation_callback_gas() -> Gas {
+        Gas(env::prepaid_gas()
+            .0
+            .saturating_sub(GAS_FOR_GET_NODES.0)
+            .saturating_sub(GAS_FOR_GET_VALIDATOR_SET.0)
+            .saturating_sub(GAS_FOR_GET_NODES_OPERATOR.0)
+            .saturating_sub(GAS_FOR_MSG_VERIFY.0))
+    }
+}
+
+#[cfg(not(feature = "single-contract"))]
+impl Contract {
+    /// Report the behavior to `node_evaluation contract` with the gas left after `reserved` for the rest of this call
+    pub(crate) fn send_scoring(&mut self, scoring: Scoring, reserved: Gas) {
+        self.report_behavior(scoring, Self::report_gas(reserved));
+    }
+
+    /// Same as `send_scoring` for each verification of a batch, in one `update_nodes_batch`
+    pub(crate) fn send_scorings(&mut self, scorings: Vec<Scoring>, reserved: Gas) {
+        let behaviors: Vec<NodeBehavior> = scorings
+            .into_iter()
+            .filter(|scoring| !scoring.is_empty())
+            .map(|scoring| {
+                self.patch_snapshot(&scoring.penalized(), &scoring.equivocators);
+                scoring.into_behavior()
+            })
+            .collect();
+        if behaviors.is_empty() {
+            return;
+        }
+        ext_ec::update_nodes_batch(
+            behaviors,
+            self.node_ev_address.clone(),
+            NO_DEPOSIT,
+            Self::report_gas(reserved),
+        );
+    }
+
+    /// The prepaid gas left after this call and `reserved`,
+    /// never less than `GAS_FOR_UPDATE_NODES` so a short budget fails the call instead of the report
+    fn report_gas(reserved: Gas) -> Gas {
+        let left = env::prepaid_gas()
+            .0
+            .saturating_sub(GAS_FOR_CREDIBILITY_CALLBACK.0)
+            .saturating_sub(reserved.0);
+        Gas(std::cmp::max(left, GAS_FOR_UPDATE_NODES.0))
+    }
+
+    pub(crate) fn report_behavior(&mut self, scoring: Scoring, gas: Gas) {
+        self.patch_snapshot(&scoring.penalized(), &scoring.equivocators);
+        ext_ec::update_nodes(
+            scoring.trusted,
+            scoring.untrusted,
+            scoring.exeception,
+            scoring.absent,
+            scoring.equivocators,
+            self.node_ev_address.clone(),
+            NO_DEPOSIT,
+            gas,
+        );
+    }
+
+    pub(crate) fn report_fraud(&mut self, trusted: Vec<NodeCredibility>, challenger: PublicKey) {
+        let slashed: Vec<PublicKey> = trusted.iter().map(|vc| vc.validator.clone()).collect();
+        self.patch_snapshot(&slashed, &[]);
+        ext_ec::punish_fraud(
+            trusted,
+            challenger,
+            self.node_ev_address.clone(),
+            NO_DEPOSIT,
+            GAS_FOR_PUNISH_FRAUD,
+        );
+    }
+
+    /// The evaluation of `keys` without a cross contract call, `None` if it must be requested
+    pub(crate) fn local_evaluation(&self, keys: &[PublicKey]) -> Option<Evaluation> {
+        self.cached_evaluation(keys)
+    }
+}
+
+#[near_bindgen]
+impl MsgVerify for Contract {
+    fn msg_verify(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        signatures: Option<Vec<Base64VecU8>>,
+    ) -> PromiseOrValue<Vec<Message>> {
+        let (msgs, invalid_signatures) = self.check_submissions(msgs, percentage, id, signatures);
+        if let Some(report) = self.verify_cached(&msgs, percentage, id, &invalid_signatures) {
+            return PromiseOrValue::Value(report.into_messages());
+        }
+        self.get_evaluation(&msgs)
+            .then(ext_self::credibility_callback(
+                msgs,
+                percentage,
+                id,
+                invalid_signatures,
+                env::current_account_id(),
+                0,
+                Self::evaluation_callback_gas(),
+            ))
+            .into()
+    }
+
+    fn msg_verify_report(
+        &mut self,
+        msgs: Vec<MessageVerify>,
+        percentage: Option<u32>,
+        id: Option<u64>,
+        signatures: Option<Vec<Base64VecU8>>,
+    ) -> PromiseOrValue<VerificationReport> {
+        let (msgs, invalid_signatures) = self.check_submissions(msgs, percentage, id, signatures);
+        if let Some(report) = self.verify_cached(&msgs, percentage, id, &invalid_signatures) {
+            return PromiseOrValue::Value(report);
+        }
+        self.get_evaluation(&msgs)
+            .then(ext_self::report_callback(
+                msgs,
+                percentage,
+                id,
+                invalid_signatures,
+                env::current_account_id(),
+                0,
+                Self::evaluation_callback_gas(),
+            ))
+            .into()
     }
 }
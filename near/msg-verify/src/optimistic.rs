@@ -0,0 +1,456 @@
+use crate::*;
+
+const GAS_FOR_CHALLENGE_RESOLVED_CALLBACK: Gas = Gas(10_000_000_000_000);
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ClaimStatus {
+    /// accepted provisionally until the challenge window of the policy closes
+    Pending,
+    /// the weighted aggregation triggered by a challenge is in progress
+    Challenged,
+    /// the weighted aggregation decided the message, see `verified`
+    Resolved,
+    /// the result was returned to `cross-chain protocol contract`
+    Finalized,
+}
+
+// The first copy of message `id` submitted by a bonded validator on an optimistic route
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct OptimisticClaim {
+    pub id: u64,
+    pub validator: PublicKey,
+    /// the credibility of `validator` when the claim was submitted
+    pub credibility: u32,
+    pub message: Message,
+    pub block_height: u64,
+    pub window: u64,
+    pub status: ClaimStatus,
+    pub challenger: Option<AccountId>,
+    /// the deposit of the challenger, returned unless the aggregation confirms the claim
+    pub challenge_bond: U128,
+    /// the message verified by the aggregation after a challenge, `None` if it failed
+    pub verified: Option<Message>,
+}
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct Bond {
+    pub owner: AccountId,
+    pub amount: U128,
+    /// claims of the validator not finalized yet, the bond can not be withdrawn until they are
+    pub locked: u32,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// set the bond required for each pending claim of a validator, and to challenge a claim
+    #[private]
+    pub fn set_optimistic_bond(&mut self, amount: U128) {
+        self.optimistic_bond = amount.0;
+    }
+
+    pub fn get_optimistic_bond(&self) -> U128 {
+        U128(self.optimistic_bond)
+    }
+
+    pub fn get_bond(&self, validator: PublicKey) -> Option<Bond> {
+        self.bonds.get(&validator)
+    }
+
+    pub fn get_optimistic_claim(&self, id: u64) -> Option<OptimisticClaim> {
+        self.optimistic_claims.get(&id)
+    }
+
+    /// @notice Called from off-chain nodes to bond the attached deposit to `env::signer_account_pk()`.
+    #[payable]
+    pub fn bond(&mut self) -> U128 {
+        let validator = env::signer_account_pk();
+        let mut bond = self.bonds.get(&validator).unwrap_or(Bond {
+            owner: env::predecessor_account_id(),
+            amount: U128(0),
+            locked: 0,
+        });
+        require!(
+            bond.owner == env::predecessor_account_id(),
+            "bonded by another account"
+        );
+        bond.amount = U128(bond.amount.0 + env::attached_deposit());
+        self.bonds.insert(&validator, &bond);
+        bond.amount
+    }
+
+    /// @notice Called from off-chain nodes to withdraw the bond of `env::signer_account_pk()` to its owner.
+    pub fn unbond(&mut self) -> Promise {
+        let validator = env::signer_account_pk();
+        let bond = self
+            .bonds
+            .get(&validator)
+            .unwrap_or_else(|| env::panic_str("no bond"));
+        require!(bond.locked == 0, "bond locked by pending claims");
+        self.bonds.remove(&validator);
+        Promise::new(bond.owner).transfer(bond.amount.0)
+    }
+
+    /// @notice Called from a bonded validator to submit message `id` on a route whose policy has an `optimistic_window`.
+    ///
+    /// @dev Only the first claim of a message is kept, it is accepted unless challenged within the window.
+    /// Only a member of the selected validator set, or a registered node if no set is selected, can claim.
+    /// A message with `sqos.reveal` is rejected, it is only verified in a commit-reveal session.
+    /// Get the validator through `env::signer_account_pk()`.
+    ///
+    /// @return True if the claim is the first one of the message.
+    pub fn submit_optimistic(&mut self, id: u64, message: Message) -> PromiseOrValue<bool> {
+        let validator = env::signer_account_pk();
+        require!(!message.sqos.reveal, "message requires commit-reveal");
+        self.claimable(&validator, &message);
+        if self.optimistic_claims.get(&id).is_some() {
+            return PromiseOrValue::Value(false);
+        }
+        if let Some(evaluation) = self.local_evaluation(std::slice::from_ref(&validator)) {
+            return PromiseOrValue::Value(self.insert_claim(id, &evaluation, validator, message));
+        }
+        self.request_evaluation(vec![validator.clone()])
+            .then(ext_self::claim_callback(
+                id,
+                validator,
+                message,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Self::evaluation_callback_gas(),
+            ))
+            .into()
+    }
+
+    #[private]
+    pub fn claim_callback(&mut self, id: u64, validator: PublicKey, message: Message) -> bool {
+        let evaluation = self.read_evaluation(std::slice::from_ref(&validator));
+        self.insert_claim(id, &evaluation, validator, message)
+    }
+
+    /// @notice Called from anyone holding a copy of message `id` conflicting with its claim, within the challenge window.
+    ///
+    /// @dev The claim and the signed copies go through the weighted aggregation of `msg_verify`,
+    /// against the whole selected validator set: validators without a copy weigh as votes for no copy, so no subset of the copies can decide alone.
+    /// Without a selected validator set the challenge reaches no verdict.
+    /// The challenger attaches a deposit of at least the optimistic bond.
+    /// If the aggregation verifies another message, the bond of the claimer goes to the challenger with its deposit.
+    /// If it verifies the claimed message, the deposit goes to the bond of the claimer.
+    /// If it reaches no verdict, or the aggregation fails, the deposit is returned and the claim is pending again.
+    ///
+    /// @param attestations Copies signed by their validators over `attestation_payload(id, message)`, at least one conflicting with the claim.
+    ///
+    /// @return The report of the aggregation, `None` if it failed.
+    #[payable]
+    pub fn challenge_optimistic(
+        &mut self,
+        id: u64,
+        attestations: Vec<Attestation>,
+    ) -> PromiseOrValue<Option<VerificationReport>> {
+        require!(
+            env::attached_deposit() >= self.optimistic_bond,
+            "challenge bond too low"
+        );
+        let mut claim = self
+            .optimistic_claims
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("claim not found"));
+        require!(claim.status == ClaimStatus::Pending, "claim not pending");
+        require!(
+            env::block_height() <= claim.block_height + claim.window,
+            "challenge window closed"
+        );
+        require!(
+            !attestations
+                .iter()
+                .any(|attestation| attestation.message.sqos.reveal),
+            "message requires commit-reveal"
+        );
+        let claim_hash = claim.message.to_hash(Some(id));
+        let mut msgs: Vec<MessageVerify> = vec![MessageVerify {
+            validator: claim.validator.clone(),
+            message: claim.message.clone(),
+        }];
+        let mut invalid_signatures: Vec<PublicKey> = Vec::new();
+        for attestation in attestations {
+            if signature::verify_ed25519(
+                &attestation.validator,
+                &attestation_payload(id, &attestation.message),
+                &attestation.signature.0,
+            ) {
+                msgs.push(MessageVerify {
+                    validator: attestation.validator,
+                    message: attestation.message,
+                });
+            } else {
+                invalid_signatures.push(attestation.validator);
+            }
+        }
+        require!(
+            msgs.iter()
+                .any(|msg| msg.message.to_hash(Some(id)) != claim_hash),
+            "no valid conflicting attestation"
+        );
+        claim.status = ClaimStatus::Challenged;
+        claim.challenger = Some(env::predecessor_account_id());
+        claim.challenge_bond = U128(env::attached_deposit());
+        self.optimistic_claims.insert(&id, &claim);
+        if let Some(evaluation) = self.local_evaluation(&Self::validators_of(&msgs)) {
+            return PromiseOrValue::Value(Some(self.resolve_challenge(
+                id,
+                &evaluation,
+                msgs,
+                invalid_signatures,
+            )));
+        }
+        self.get_evaluation(&msgs)
+            .then(ext_self::challenge_callback(
+                id,
+                msgs,
+                invalid_signatures,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Gas(Self::evaluation_callback_gas()
+                    .0
+                    .saturating_sub(GAS_FOR_CHALLENGE_RESOLVED_CALLBACK.0)),
+            ))
+            .then(ext_self::challenge_resolved_callback(
+                id,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                GAS_FOR_CHALLENGE_RESOLVED_CALLBACK,
+            ))
+            .into()
+    }
+
+    #[private]
+    pub fn challenge_callback(
+        &mut self,
+        id: u64,
+        msgs: Vec<MessageVerify>,
+        invalid_signatures: Vec<PublicKey>,
+    ) -> VerificationReport {
+        let evaluation = self.read_evaluation(&Self::validators_of(&msgs));
+        self.resolve_challenge(id, &evaluation, msgs, invalid_signatures)
+    }
+
+    /// Put a claim left `Challenged` by a failed `challenge_callback` back to pending and return the deposit of the challenger.
+    ///
+    /// @return The report of the challenge, `None` if it failed.
+    #[private]
+    pub fn challenge_resolved_callback(&mut self, id: u64) -> Option<VerificationReport> {
+        require!(env::promise_results_count() == 1);
+        if let PromiseResult::Successful(result) = env::promise_result(0) {
+            return near_sdk::serde_json::from_slice::<VerificationReport>(&result).ok();
+        }
+        let mut claim = self.optimistic_claims.get(&id).unwrap();
+        if claim.status != ClaimStatus::Challenged {
+            return None;
+        }
+        log!("challenge of claim {} failed", id);
+        let challenger = claim.challenger.take().unwrap();
+        let challenge_bond = std::mem::replace(&mut claim.challenge_bond, U128(0)).0;
+        claim.status = ClaimStatus::Pending;
+        self.optimistic_claims.insert(&id, &claim);
+        if challenge_bond > 0 {
+            Promise::new(challenger).transfer(challenge_bond);
+        }
+        None
+    }
+
+    /// @notice Called from `cross-chain protocol contract` to get the result of optimistic message `id`.
+    ///
+    /// @dev A claim not challenged within the window, or whose challenge was undecided, is accepted and its validator rewarded as trusted.
+    /// The accepted claim is recorded in the audit log and delivered like the verdict of a challenge.
+    /// The accepted message can then be challenged with a fraud proof like any verified message, see `submit_fraud_proof`.
+    ///
+    /// @return The accepted message. The `Vec` will be empty if the aggregation after a challenge failed.
+    pub fn finalize_optimistic(&mut self, id: u64) -> Vec<Message> {
+        assert_eq!(env::predecessor_account_id(), self.cross_contract_id);
+        let mut claim = self
+            .optimistic_claims
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("claim not found"));
+        let result = match claim.status {
+            ClaimStatus::Pending => {
+                require!(
+                    env::block_height() > claim.block_height + claim.window,
+                    "challenge window not closed"
+                );
+                let mut bond = self.bonds.get(&claim.validator).unwrap();
+                bond.locked -= 1;
+                self.bonds.insert(&claim.validator, &bond);
+                let policy = self.resolve_policy(claim.message.clone());
+                let threshold = self.resolve_threshold(None, &policy);
+                let trusted = vec![NodeCredibility {
+                    validator: claim.validator.clone(),
+                    credibility_value: claim.credibility,
+                }];
+                // the claim is accepted on its own, no copy was aggregated
+                let mut report = VerificationReport::new(VerificationOutcome::Verified);
+                report.message = Some(claim.message.clone());
+                report.message_hash = Some(claim.message.to_hash(Some(id)));
+                report.snapshot = trusted.clone();
+                report.threshold = threshold;
+                self.record_audit(Some(id), &report);
+                self.deliver(Some(id), &report);
+                self.record_verified(
+                    Some(id),
+                    claim.message.clone(),
+                    threshold,
+                    trusted,
+                    // the claim is signed by the transaction of its validator
+                    true,
+                );
+                self.report_behavior(
+                    Scoring {
+                        trusted: vec![claim.validator.clone()],
+                        ..Default::default()
+                    },
+                    GAS_FOR_UPDATE_NODES,
+                );
+                vec![claim.message.clone()]
+            }
+            ClaimStatus::Resolved => claim.verified.clone().into_iter().collect(),
+            ClaimStatus::Challenged => env::panic_str("challenge in progress"),
+            ClaimStatus::Finalized => env::panic_str("claim already finalized"),
+        };
+        claim.status = ClaimStatus::Finalized;
+        self.optimistic_claims.insert(&id, &claim);
+        result
+    }
+}
+
+impl Contract {
+    /// Check `validator` can claim `message`: its route is optimistic and the bond of the validator backs one more claim
+    fn claimable(&self, validator: &PublicKey, message: &Message) -> (Bond, u64) {
+        let window = self.resolve_policy(message.clone()).optimistic_window;
+        require!(window > 0, "route not optimistic");
+        let bond = self
+            .bonds
+            .get(validator)
+            .unwrap_or_else(|| env::panic_str("no bond"));
+        // every pending claim is backed by its own bond
+        require!(
+            bond.amount.0 >= self.optimistic_bond * (bond.locked as u128 + 1),
+            "bond too low"
+        );
+        (bond, window)
+    }
+
+    /// Keep the first claim of message `id` if `validator` is a member, see `is_member`
+    fn insert_claim(
+        &mut self,
+        id: u64,
+        evaluation: &Evaluation,
+        validator: PublicKey,
+        message: Message,
+    ) -> bool {
+        require!(
+            Self::is_member(evaluation, &validator),
+            "not a member of the selected validator set"
+        );
+        let credibility = evaluation
+            .credibility
+            .as_ref()
+            .unwrap_or_else(|| env::panic_str("get nodes credibility failed"))
+            .iter()
+            .find(|vc| vc.validator == validator)
+            .map_or(0, |vc| vc.credibility_value);
+        if self.optimistic_claims.get(&id).is_some() {
+            return false;
+        }
+        let (mut bond, window) = self.claimable(&validator, &message);
+        bond.locked += 1;
+        self.bonds.insert(&validator, &bond);
+        emit_event(
+            "optimistic_claim",
+            json!([{ "id": id, "validator": validator, "message_hash": message.to_hash(Some(id)) }]),
+        );
+        self.optimistic_claims.insert(
+            &id,
+            &OptimisticClaim {
+                id,
+                validator,
+                credibility,
+                message,
+                block_height: env::block_height(),
+                window,
+                status: ClaimStatus::Pending,
+                challenger: None,
+                challenge_bond: U128(0),
+                verified: None,
+            },
+        );
+        true
+    }
+
+    /// Weigh the claim and the attestations of message `id` by `evaluation`, then settle the bonds
+    fn resolve_challenge(
+        &mut self,
+        id: u64,
+        evaluation: &Evaluation,
+        msgs: Vec<MessageVerify>,
+        invalid_signatures: Vec<PublicKey>,
+    ) -> VerificationReport {
+        let report = match evaluation.validator_set {
+            // validators are not expected to submit a copy of an optimistic message
+            // the claim is submitted by its validator and the attestations are signed
+            Some(_) => self.score_copies(
+                evaluation,
+                msgs,
+                None,
+                Some(id),
+                Some(invalid_signatures),
+                Absentees::Weighed,
+            ),
+            None => {
+                log!("no validator set selected to weigh the challenge");
+                VerificationReport::new(VerificationOutcome::EvaluationFailed)
+            }
+        };
+        self.record_audit(Some(id), &report);
+        let mut claim = self.optimistic_claims.get(&id).unwrap();
+        let challenger = claim.challenger.clone().unwrap();
+        let challenge_bond = std::mem::replace(&mut claim.challenge_bond, U128(0)).0;
+        if report.outcome != VerificationOutcome::Verified {
+            // no verdict, the claim stays locked until it is finalized or challenged again
+            log!("challenge of claim {} undecided", id);
+            claim.status = ClaimStatus::Pending;
+            claim.challenger = None;
+            self.optimistic_claims.insert(&id, &claim);
+            if challenge_bond > 0 {
+                Promise::new(challenger).transfer(challenge_bond);
+            }
+            return report;
+        }
+        let mut bond = self.bonds.get(&claim.validator).unwrap();
+        bond.locked -= 1;
+        if report.message.as_ref() != Some(&claim.message) {
+            let slashed = std::cmp::min(bond.amount.0, self.optimistic_bond);
+            bond.amount = U128(bond.amount.0 - slashed);
+            emit_event(
+                "optimistic_claim_overturned",
+                json!([{ "id": id, "validator": claim.validator, "challenger": challenger, "slashed": U128(slashed) }]),
+            );
+            Promise::new(challenger).transfer(slashed + challenge_bond);
+        } else {
+            bond.amount = U128(bond.amount.0 + challenge_bond);
+        }
+        self.bonds.insert(&claim.validator, &bond);
+        claim.status = ClaimStatus::Resolved;
+        claim.verified = report.message.clone();
+        self.optimistic_claims.insert(&id, &claim);
+        self.deliver(Some(id), &report);
+        report
+    }
+
+    /// The bond of `validator` if it weighs in `voting_power`, 0 otherwise
+    pub(crate) fn stake_of(&self, validator: &PublicKey, voting_power: &VotingPower) -> Balance {
+        match voting_power {
+            VotingPower::StakeWeighted => self.bonds.get(validator).map_or(0, |bond| bond.amount.0),
+            _ => 0,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::*;
+
+// phase lengths of the default policy, in blocks
+const DEFAULT_COMMIT_BLOCKS: u64 = 20;
+const DEFAULT_REVEAL_BLOCKS: u64 = 20;
+
+// The route a policy applies to, `contract` is the destination `content.contract` or `None` for the whole route
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct Route {
+    pub from_chain: String,
+    pub to_chain: String,
+    pub contract: Option<String>,
+}
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct VerificationPolicy {
+    /// [0~10000], minimum credibility weight of the trusted group, raised to the contract-wide `credibility_weight_threshold`
+    pub credibility_weight_threshold: u32,
+    /// minimum number of distinct validators in the trusted group
+    pub min_validators: u32,
+    /// minimum number of distinct operators of the validators in the trusted group
+    pub min_operators: u32,
+    /// minimum sum of the credibility of the trusted group
+    pub min_credibility: u64,
+    /// blocks an optimistic claim can be challenged, 0 to verify messages of the route by the committee only
+    pub optimistic_window: u64,
+    /// how the copy of each validator weighs in the aggregation
+    pub voting_power: VotingPower,
+    /// [0~10000], the leading group is not trusted if the weight of the second one is within this margin
+    pub ambiguity_margin: u32,
+    /// blocks a commit-reveal session accepts commitments, see `open_reveal_session`
+    pub commit_blocks: u64,
+    /// blocks a commit-reveal session accepts reveals after the commit phase
+    pub reveal_blocks: u64,
+}
+
+impl VerificationPolicy {
+    /// Whether the weights of the top two groups are too close to tell the message, an exact tie always is
+    pub fn is_ambiguous(&self, groups: &[GroupReport]) -> bool {
+        match groups {
+            [leading, second, ..] => {
+                leading.credibility_weight - second.credibility_weight <= self.ambiguity_margin
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the group is large, diverse and credible enough to be trusted
+    pub fn is_satisfied_by(&self, group: &GroupReport) -> bool {
+        group.validators.len() as u64 >= self.min_validators as u64
+            && group.operators >= self.min_operators
+            && group.credibility >= self.min_credibility
+    }
+}
+
+/// The number of distinct operators of `validators`, validators without a known operator are not counted
+pub(crate) fn distinct_operators(
+    validators: &[PublicKey],
+    operators: &HashMap<PublicKey, AccountId>,
+) -> u32 {
+    let mut distinct: Vec<&AccountId> = Vec::new();
+    for operator in validators.iter().filter_map(|pk| operators.get(pk)) {
+        if !distinct.contains(&operator) {
+            distinct.push(operator);
+        }
+    }
+    distinct.len() as u32
+}
+
+#[near_bindgen]
+impl Contract {
+    /// set the policy of messages from `from_chain` to `to_chain`, only to the destination `contract` if given
+    #[private]
+    pub fn set_policy(
+        &mut self,
+        from_chain: String,
+        to_chain: String,
+        contract: Option<String>,
+        policy: VerificationPolicy,
+    ) {
+        require!(
+            policy.credibility_weight_threshold <= 10000,
+            "threshold out of range"
+        );
+        require!(policy.voting_power.is_valid(), "invalid voting power");
+        require!(policy.ambiguity_margin <= 10000, "margin out of range");
+        self.policies.insert(
+            &Route {
+                from_chain,
+                to_chain,
+                contract,
+            },
+            &policy,
+        );
+    }
+
+    #[private]
+    pub fn remove_policy(
+        &mut self,
+        from_chain: String,
+        to_chain: String,
+        contract: Option<String>,
+    ) -> Option<VerificationPolicy> {
+        self.policies.remove(&Route {
+            from_chain,
+            to_chain,
+            contract,
+        })
+    }
+
+    /// the policy set for exactly this route, without fallback
+    pub fn get_policy(
+        &self,
+        from_chain: String,
+        to_chain: String,
+        contract: Option<String>,
+    ) -> Option<VerificationPolicy> {
+        self.policies.get(&Route {
+            from_chain,
+            to_chain,
+            contract,
+        })
+    }
+
+    pub fn get_policies(&self, from_index: u64, limit: u64) -> Vec<(Route, VerificationPolicy)> {
+        let keys = self.policies.keys_as_vector();
+        let values = self.policies.values_as_vector();
+        (from_index..std::cmp::min(from_index + limit, self.policies.len()))
+            .map(|index| (keys.get(index).unwrap(), values.get(index).unwrap()))
+            .collect()
+    }
+
+    /// @notice The policy applied to `message`.
+    ///
+    /// @dev The policy of the destination contract takes precedence over the policy of the route,
+    /// which takes precedence over the default policy of `credibility_weight_threshold` without quorum.
+    pub fn resolve_policy(&self, message: Message) -> VerificationPolicy {
+        self.resolve_route_policy(Route {
+            from_chain: message.from_chain,
+            to_chain: message.to_chain,
+            contract: Some(message.content.contract),
+        })
+    }
+}
+
+impl Contract {
+    /// Same as `resolve_policy` for the route of a message, `route.contract` is tried first if given.
+    pub(crate) fn resolve_route_policy(&self, mut route: Route) -> VerificationPolicy {
+        if let Some(policy) = self.policies.get(&route) {
+            return policy;
+        }
+        route.contract = None;
+        self.policies.get(&route).unwrap_or(VerificationPolicy {
+            credibility_weight_threshold: self.credibility_weight_threshold,
+            min_validators: 0,
+            min_operators: 0,
+            min_credibility: 0,
+            optimistic_window: 0,
+            voting_power: VotingPower::Linear,
+            ambiguity_margin: 0,
+            commit_blocks: DEFAULT_COMMIT_BLOCKS,
+            reveal_blocks: DEFAULT_REVEAL_BLOCKS,
+        })
+    }
+}
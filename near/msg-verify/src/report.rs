@@ -0,0 +1,108 @@
+use crate::*;
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum VerificationOutcome {
+    /// the leading group reached the threshold and the quorum of the policy
+    Verified,
+    /// the weight of the leading group is below the threshold
+    ThresholdNotMet,
+    /// the leading group reached the threshold but the second one is within the ambiguity margin of the policy
+    /// or the lead changes when the copies are tallied with the voting power of the route of the leading copy
+    Ambiguous,
+    /// the leading group reached the threshold but not the quorum of the policy
+    QuorumNotMet,
+    /// no copy was submitted
+    NoSubmissions,
+    /// no copy left after excluding invalid signatures, non-members and equivocators
+    NoValidCopy,
+    /// the validators of every copy have no credibility and the bootstrap majority is disabled or no validator set is selected
+    NoCredibleSubmitters,
+    /// the credibility of the validators could not be read from `node_evaluation contract`, or no validator set is selected to weigh a challenge against
+    EvaluationFailed,
+    /// the aggregate signature of the signers does not verify
+    InvalidSignature,
+}
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ExclusionReason {
+    InvalidSignature,
+    Equivocation,
+    /// outside the selected validator set, or not registered if no validator set is selected
+    NonMember,
+}
+
+// A submission left out of the aggregation
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct ExcludedSubmission {
+    pub validator: PublicKey,
+    pub reason: ExclusionReason,
+}
+
+// Validators who submitted identical copies
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct GroupReport {
+    pub message_hash: String,
+    pub validators: Vec<PublicKey>,
+    /// number of distinct operators of `validators`
+    pub operators: u32,
+    pub credibility: u64,
+    /// voting power of the group under the `voting_power` of the policy
+    pub power: U128,
+    /// [0~10000], voting power of the group over `total_power`
+    pub credibility_weight: u32,
+}
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct VerificationReport {
+    pub outcome: VerificationOutcome,
+    /// the verified message, `None` unless the outcome is `Verified`
+    pub message: Option<Message>,
+    /// hash of the copy of the leading group
+    pub message_hash: Option<String>,
+    /// groups sorted by weight, the leading group first
+    pub groups: Vec<GroupReport>,
+    pub total_credibility: u64,
+    pub total_power: U128,
+    /// credibility of the validators read from `node_evaluation contract`
+    pub snapshot: Vec<NodeCredibility>,
+    /// the threshold applied to the leading group, 0 if no copy was aggregated
+    pub threshold: u32,
+    pub excluded: Vec<ExcludedSubmission>,
+}
+
+impl VerificationReport {
+    pub fn new(outcome: VerificationOutcome) -> Self {
+        Self {
+            outcome,
+            message: None,
+            message_hash: None,
+            groups: Vec::new(),
+            total_credibility: 0,
+            total_power: U128(0),
+            snapshot: Vec::new(),
+            threshold: 0,
+            excluded: Vec::new(),
+        }
+    }
+
+    pub fn exclude(&mut self, validators: &[PublicKey], reason: ExclusionReason) {
+        self.excluded
+            .extend(validators.iter().map(|validator| ExcludedSubmission {
+                validator: validator.clone(),
+                reason: reason.clone(),
+            }));
+    }
+
+    /// The result of `msg_verify`, the verified message or an empty `Vec`
+    pub fn into_messages(self) -> Vec<Message> {
+        match self.outcome {
+            VerificationOutcome::Verified => self.message.into_iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,547 @@
+use crate::*;
+
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SessionStatus {
+    /// accepting copies, the aggregation is evaluated again on each arrival
+    Open,
+    /// the threshold was reached or the deadline passed, see `report`
+    Finalized,
+}
+
+// The commitment of a validator to its copy in a commit-reveal session, see `commitment`
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct Commitment {
+    pub validator: PublicKey,
+    pub hash: Base64VecU8,
+}
+
+// The copies of message `id` submitted one by one by the validators
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct Session {
+    pub id: u64,
+    pub percentage: Option<u32>,
+    /// the last block height accepting copies, or reveals in a commit-reveal session, before the session is finalized with what it has
+    pub deadline: u64,
+    /// the last block height accepting commitments, `None` if the copies are submitted in the clear
+    pub commit_deadline: Option<u64>,
+    pub commitments: Vec<Commitment>,
+    pub status: SessionStatus,
+    pub submissions: Vec<MessageVerify>,
+    /// the latest evaluation of the aggregation, the final one once `Finalized`
+    pub report: Option<VerificationReport>,
+    /// validators who submitted after the session was finalized, scored against its outcome
+    pub late: Vec<MessageVerify>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// @notice Called from `cross-chain protocol contract` to open the session of message `id`.
+    ///
+    /// @param percentage Same as `msg_verify`.
+    /// @param duration The number of blocks the session accepts copies unless the threshold is reached before.
+    pub fn open_session(&mut self, id: u64, percentage: Option<u32>, duration: u64) {
+        assert_eq!(env::predecessor_account_id(), self.cross_contract_id);
+        require!(percentage.unwrap_or(0) <= 10000, "percentage out of range");
+        self.insert_session(id, percentage, None, env::block_height() + duration);
+    }
+
+    /// @notice Called from `cross-chain protocol contract` to open the session of message `id` when its `sqos.reveal` is set.
+    ///
+    /// @dev Validators first commit to their copy with `commit_copy`, so no one can copy the others, then reveal it with `reveal_copy`.
+    /// The phases last `commit_blocks` and `reveal_blocks` of the policy of the route, see `resolve_policy`.
+    ///
+    /// @param percentage Same as `msg_verify`.
+    /// @param contract The destination `content.contract` of the message, the policy of the route applies if it has none.
+    pub fn open_reveal_session(
+        &mut self,
+        id: u64,
+        percentage: Option<u32>,
+        from_chain: String,
+        to_chain: String,
+        contract: Option<String>,
+    ) {
+        assert_eq!(env::predecessor_account_id(), self.cross_contract_id);
+        require!(percentage.unwrap_or(0) <= 10000, "percentage out of range");
+        let policy = self.resolve_route_policy(Route {
+            from_chain,
+            to_chain,
+            contract,
+        });
+        let commit_deadline = env::block_height() + policy.commit_blocks;
+        self.insert_session(
+            id,
+            percentage,
+            Some(commit_deadline),
+            commit_deadline + policy.reveal_blocks,
+        );
+    }
+
+    pub fn get_session(&self, id: u64) -> Option<Session> {
+        self.sessions.get(&id)
+    }
+
+    /// @notice Called from a validator to submit its copy of message `id`.
+    ///
+    /// @dev Get the validator through `env::signer_account_pk()`.
+    /// Only a member of the selected validator set, or a registered node if no set is selected, can submit.
+    /// While the session is open, the aggregation is evaluated again with the new copy.
+    /// The session is finalized before the deadline once the leading group reaches the threshold
+    /// over the credibility of the copies and of the selected validators who did not submit yet.
+    /// After it is finalized, the copy is scored against the verified message right away.
+    ///
+    /// @return The latest evaluation of the session, the final one if it is finalized.
+    pub fn submit_copy(&mut self, id: u64, message: Message) -> PromiseOrValue<VerificationReport> {
+        let copy = MessageVerify {
+            validator: env::signer_account_pk(),
+            message,
+        };
+        self.submittable_session(id, &copy);
+        if let Some(evaluation) = self.local_evaluation(std::slice::from_ref(&copy.validator)) {
+            return self.insert_copy(id, &evaluation, copy);
+        }
+        self.request_evaluation(vec![copy.validator.clone()])
+            .then(ext_self::submit_callback(
+                id,
+                copy,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Self::evaluation_callback_gas(),
+            ))
+            .into()
+    }
+
+    /// @notice Called from a validator to commit to its copy of message `id` during the commit phase.
+    ///
+    /// @dev Only a member of the selected validator set can commit, see `get_current_validator_set`.
+    ///
+    /// @param hash `commitment(id, message, validator, salt)` of the copy and a secret salt.
+    pub fn commit_copy(&mut self, id: u64, hash: Base64VecU8) -> PromiseOrValue<()> {
+        let commitment = Commitment {
+            validator: env::signer_account_pk(),
+            hash,
+        };
+        self.committable_session(id, &commitment);
+        if let Some(evaluation) = self.local_evaluation(std::slice::from_ref(&commitment.validator))
+        {
+            self.insert_commitment(id, &evaluation, commitment);
+            return PromiseOrValue::Value(());
+        }
+        self.request_evaluation(vec![commitment.validator.clone()])
+            .then(ext_self::commit_callback(
+                id,
+                commitment,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Self::evaluation_callback_gas(),
+            ))
+            .into()
+    }
+
+    /// @notice Called from a validator to reveal its committed copy of message `id` after the commit phase.
+    ///
+    /// @dev Only a copy matching the commitment of the validator is accepted, then the aggregation is evaluated again.
+    /// The session is finalized once every commitment is revealed or after the deadline,
+    /// validators who committed without revealing, and the selected ones who never committed, are reported as absent.
+    pub fn reveal_copy(
+        &mut self,
+        id: u64,
+        message: Message,
+        salt: Base64VecU8,
+    ) -> PromiseOrValue<VerificationReport> {
+        let validator = env::signer_account_pk();
+        let mut session = self
+            .sessions
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("session not found"));
+        let commit_deadline = session
+            .commit_deadline
+            .unwrap_or_else(|| env::panic_str("session without commit phase"));
+        require!(
+            session.status == SessionStatus::Open,
+            "session already finalized"
+        );
+        require!(
+            env::block_height() > commit_deadline,
+            "commit phase not over"
+        );
+        require!(env::block_height() <= session.deadline, "reveal phase over");
+        require!(message.sqos.reveal, "message without reveal");
+        let committed = session
+            .commitments
+            .iter()
+            .find(|committed| committed.validator == validator)
+            .unwrap_or_else(|| env::panic_str("copy not committed"));
+        require!(
+            committed.hash.0 == commitment(id, &message, &validator, &salt.0),
+            "copy does not match the commitment"
+        );
+        require!(
+            !session
+                .submissions
+                .iter()
+                .any(|msg| msg.validator == validator),
+            "copy already revealed"
+        );
+        session
+            .submissions
+            .push(MessageVerify { validator, message });
+        self.sessions.insert(&id, &session);
+        self.evaluate_session(session)
+    }
+
+    /// @notice Finalize the session of message `id` with the copies it has, anyone can call it after the deadline.
+    pub fn close_session(&mut self, id: u64) -> PromiseOrValue<VerificationReport> {
+        let session = self
+            .sessions
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("session not found"));
+        require!(
+            session.status == SessionStatus::Open,
+            "session already finalized"
+        );
+        require!(
+            env::block_height() > session.deadline,
+            "session deadline not passed"
+        );
+        self.evaluate_session(session)
+    }
+
+    #[private]
+    pub fn submit_callback(
+        &mut self,
+        id: u64,
+        copy: MessageVerify,
+    ) -> PromiseOrValue<VerificationReport> {
+        let evaluation = self.read_evaluation(std::slice::from_ref(&copy.validator));
+        self.insert_copy(id, &evaluation, copy)
+    }
+
+    #[private]
+    pub fn commit_callback(&mut self, id: u64, commitment: Commitment) {
+        let evaluation = self.read_evaluation(std::slice::from_ref(&commitment.validator));
+        self.insert_commitment(id, &evaluation, commitment);
+    }
+
+    #[private]
+    pub fn session_callback(&mut self, id: u64, msgs: Vec<MessageVerify>) -> VerificationReport {
+        let evaluation = self.read_evaluation(&Self::validators_of(&msgs));
+        self.conclude_session(id, &evaluation, msgs)
+    }
+}
+
+impl Contract {
+    /// Evaluate the aggregation of `msgs` in session `id`, finalize it if it is decided or expired
+    /// Copies submitted while the evaluation of `msgs` was pending are scored against the final outcome like late ones
+    fn conclude_session(
+        &mut self,
+        id: u64,
+        evaluation: &Evaluation,
+        msgs: Vec<MessageVerify>,
+    ) -> VerificationReport {
+        let mut session = self.sessions.get(&id).unwrap();
+        if session.status == SessionStatus::Finalized {
+            // finalized by an evaluation that resolved earlier
+            return session.report.unwrap();
+        }
+        let expired = env::block_height() > session.deadline;
+        let non_revealers = Self::non_revealers(&session, &msgs);
+        let submitters = Self::validators_of(&msgs);
+        // copies submitted while the evaluation of `msgs` was pending
+        let unevaluated: Vec<MessageVerify> = session
+            .submissions
+            .iter()
+            .filter(|copy| !msgs.iter().any(|msg| msg.validator == copy.validator))
+            .cloned()
+            .collect();
+        // once every committer revealed, the selected validators who never committed can not take part anymore
+        let revealed = session.commit_deadline.is_some() && non_revealers.is_empty();
+        // otherwise selected validators may still submit until the deadline
+        let (report, mut scoring) = self.aggregate_copies(
+            evaluation,
+            msgs,
+            session.percentage,
+            Some(id),
+            Some(Vec::new()),
+            if expired || revealed {
+                Absentees::Reported
+            } else {
+                Absentees::Ignored
+            },
+        );
+        let decided = match session.commit_deadline {
+            Some(_) => revealed,
+            None => self.is_decided(evaluation, &submitters, &report),
+        };
+        if expired || decided {
+            for validator in non_revealers {
+                if !scoring.absent.contains(&validator) {
+                    scoring.absent.push(validator);
+                }
+            }
+            // scored on their own against the outcome
+            scoring
+                .absent
+                .retain(|pk| !unevaluated.iter().any(|copy| copy.validator == *pk));
+            self.apply_scoring(Some(id), &report, scoring);
+            self.score_late(evaluation, &report, &unevaluated);
+            self.record_audit(Some(id), &report);
+            self.deliver(Some(id), &report);
+            session.status = SessionStatus::Finalized;
+            emit_event(
+                "session_finalized",
+                json!([{ "id": id, "outcome": report.outcome, "message_hash": report.message_hash }]),
+            );
+        }
+        session.report = Some(report.clone());
+        self.sessions.insert(&id, &session);
+        report
+    }
+
+    /// The session of message `id` if it still accepts `copy`
+    fn submittable_session(&self, id: u64, copy: &MessageVerify) -> Session {
+        let session = self
+            .sessions
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("session not found"));
+        require!(
+            session.commit_deadline.is_none(),
+            "copies of the session must be committed"
+        );
+        require!(!copy.message.sqos.reveal, "message requires commit-reveal");
+        require!(
+            !session
+                .submissions
+                .iter()
+                .chain(session.late.iter())
+                .any(|msg| msg.validator == copy.validator && msg.message == copy.message),
+            "copy already submitted"
+        );
+        if session.status == SessionStatus::Finalized {
+            require!(
+                !session
+                    .submissions
+                    .iter()
+                    .chain(session.late.iter())
+                    .any(|msg| msg.validator == copy.validator),
+                "validator already scored"
+            );
+        }
+        session
+    }
+
+    /// Add the copy of a member to session `id`, non-members never bloat the session
+    fn insert_copy(
+        &mut self,
+        id: u64,
+        evaluation: &Evaluation,
+        copy: MessageVerify,
+    ) -> PromiseOrValue<VerificationReport> {
+        let mut session = self.submittable_session(id, &copy);
+        require!(
+            Self::non_members(evaluation, std::slice::from_ref(&copy)).is_empty(),
+            "not a member of the selected validator set"
+        );
+        if session.status == SessionStatus::Finalized {
+            self.score_late(
+                evaluation,
+                session.report.as_ref().unwrap(),
+                std::slice::from_ref(&copy),
+            );
+            session.late.push(copy);
+            self.sessions.insert(&id, &session);
+            return PromiseOrValue::Value(session.report.unwrap());
+        }
+        session.submissions.push(copy);
+        self.sessions.insert(&id, &session);
+        self.evaluate_session(session)
+    }
+
+    /// The session of message `id` if it still accepts `commitment`
+    fn committable_session(&self, id: u64, commitment: &Commitment) -> Session {
+        let session = self
+            .sessions
+            .get(&id)
+            .unwrap_or_else(|| env::panic_str("session not found"));
+        let commit_deadline = session
+            .commit_deadline
+            .unwrap_or_else(|| env::panic_str("session without commit phase"));
+        require!(env::block_height() <= commit_deadline, "commit phase over");
+        require!(commitment.hash.0.len() == 32, "invalid commitment");
+        require!(
+            !session
+                .commitments
+                .iter()
+                .any(|committed| committed.validator == commitment.validator),
+            "copy already committed"
+        );
+        session
+    }
+
+    fn insert_commitment(&mut self, id: u64, evaluation: &Evaluation, commitment: Commitment) {
+        let mut session = self.committable_session(id, &commitment);
+        require!(
+            matches!(&evaluation.validator_set, Some(validator_set) if validator_set.contains(&commitment.validator)),
+            "not a member of the selected validator set"
+        );
+        session.commitments.push(commitment);
+        self.sessions.insert(&id, &session);
+    }
+
+    fn insert_session(
+        &mut self,
+        id: u64,
+        percentage: Option<u32>,
+        commit_deadline: Option<u64>,
+        deadline: u64,
+    ) {
+        require!(self.sessions.get(&id).is_none(), "session already opened");
+        self.expire_sessions();
+        self.session_expiry.push(id);
+        self.sessions.insert(
+            &id,
+            &Session {
+                id,
+                percentage,
+                deadline,
+                commit_deadline,
+                commitments: Vec::new(),
+                status: SessionStatus::Open,
+                submissions: Vec::new(),
+                report: None,
+                late: Vec::new(),
+            },
+        );
+    }
+
+    /// Remove some of the finalized sessions whose deadline passed more than `challenge_window` blocks ago,
+    /// until then late copies are still scored
+    fn expire_sessions(&mut self) {
+        let now = env::block_height();
+        let expired = self.session_expiry.pop_expired(
+            now.saturating_sub(self.challenge_window),
+            MAX_EXPIRE_PER_INSERT,
+        );
+        for id in expired {
+            match self.sessions.get(&id) {
+                Some(session)
+                    if session.status == SessionStatus::Finalized
+                        && session.deadline + self.challenge_window < now =>
+                {
+                    self.sessions.remove(&id);
+                }
+                // still open or within the window, checked again later
+                Some(_) => self.session_expiry.push(id),
+                None => {}
+            }
+        }
+    }
+
+    fn evaluate_session(&mut self, session: Session) -> PromiseOrValue<VerificationReport> {
+        if let Some(evaluation) = self.local_evaluation(&Self::validators_of(&session.submissions))
+        {
+            return PromiseOrValue::Value(self.conclude_session(
+                session.id,
+                &evaluation,
+                session.submissions,
+            ));
+        }
+        self.get_evaluation(&session.submissions)
+            .then(ext_self::session_callback(
+                session.id,
+                session.submissions,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                Self::evaluation_callback_gas(),
+            ))
+            .into()
+    }
+
+    /// The validators who committed to a copy without revealing it in `msgs`
+    fn non_revealers(session: &Session, msgs: &[MessageVerify]) -> Vec<PublicKey> {
+        session
+            .commitments
+            .iter()
+            .filter(|committed| !msgs.iter().any(|msg| msg.validator == committed.validator))
+            .map(|committed| committed.validator.clone())
+            .collect()
+    }
+
+    /// The leading group keeps the threshold even if every selected validator but the `submitters` of the report submits another copy.
+    /// Their voting power is counted by the policy of the verified message like in the tally of the report,
+    /// at its most when it depends on the other copies.
+    fn is_decided(
+        &self,
+        evaluation: &Evaluation,
+        submitters: &[PublicKey],
+        report: &VerificationReport,
+    ) -> bool {
+        let (message, validator_set) = match (&report.message, &evaluation.validator_set) {
+            (Some(message), Some(validator_set))
+                if report.outcome == VerificationOutcome::Verified =>
+            {
+                (message, validator_set)
+            }
+            _ => return false,
+        };
+        // verified by the bootstrap majority, wait for the deadline
+        if report.total_credibility == 0 {
+            return false;
+        }
+        let voting_power = self.resolve_policy(message.clone()).voting_power;
+        let pending: Vec<Vote> = validator_set
+            .validators
+            .iter()
+            .filter(|vc| !submitters.contains(&vc.validator))
+            .map(|vc| Vote {
+                validator: vc.validator.clone(),
+                message_hash: String::new(),
+                credibility: vc.credibility_value,
+                stake: self.stake_of(&vc.validator, &voting_power),
+                operator: None,
+            })
+            .collect();
+        let total_power = match voting_power {
+            // the cap grows with the credibility of the copies, a validator weighs its credibility at most
+            VotingPower::Capped { .. } => (report.total_credibility as u128)
+                .saturating_add(tally(&pending, &VotingPower::Linear).total_power),
+            // each of them may bring one more operator to the group of its copy
+            VotingPower::PerOperator => report.total_power.0.saturating_add(pending.len() as u128),
+            _ => report
+                .total_power
+                .0
+                .saturating_add(tally(&pending, &voting_power).total_power),
+        };
+        credibility_weight(report.groups[0].power.0, total_power) >= report.threshold
+    }
+
+    /// A late copy of the verified message is trusted, any other copy is untrusted.
+    /// Late copies of a session that verified nothing are kept without being scored,
+    /// so are the copies of validators who are not members by `evaluation`, see `non_members`.
+    fn score_late(
+        &mut self,
+        evaluation: &Evaluation,
+        report: &VerificationReport,
+        copies: &[MessageVerify],
+    ) {
+        let non_members = Self::non_members(evaluation, copies);
+        let mut scoring = Scoring::default();
+        if let Some(message) = report.message.as_ref() {
+            for copy in copies
+                .iter()
+                .filter(|copy| !non_members.contains(&copy.validator))
+            {
+                if copy.message == *message {
+                    scoring.trusted.push(copy.validator.clone());
+                } else {
+                    scoring.untrusted.push(copy.validator.clone());
+                }
+            }
+        }
+        if !scoring.is_empty() {
+            self.report_behavior(scoring, GAS_FOR_UPDATE_NODES);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::attestation_payload;
+use cross_chain::MessageVerify;
+use ed25519_dalek::Verifier;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{CurveType, PublicKey};
+use std::convert::TryFrom;
+
+/// Verify the ed25519 `signature` of `pk` over `data`.
+/// Return false for keys of other curves and malformed keys or signatures.
+pub fn verify_ed25519(pk: &PublicKey, data: &[u8], signature: &[u8]) -> bool {
+    if pk.curve_type() != CurveType::ED25519 {
+        return false;
+    }
+    // the first byte of `PublicKey` is the curve type
+    let public_key = match ed25519_dalek::PublicKey::from_bytes(&pk.as_bytes()[1..]) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    let signature = match ed25519_dalek::Signature::try_from(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    public_key.verify(data, &signature).is_ok()
+}
+
+/// Split the copies of message `id` into the ones signed by their validator and the ones with a bad signature.
+/// `signatures[i]` is the signature of `msgs[i]` over `attestation_payload(id, msgs[i].message)`.
+pub fn verify_submissions(
+    msgs: Vec<MessageVerify>,
+    signatures: &[Base64VecU8],
+    id: u64,
+) -> (Vec<MessageVerify>, Vec<MessageVerify>) {
+    assert_eq!(
+        msgs.len(),
+        signatures.len(),
+        "one signature is required for each copy"
+    );
+    let mut valid: Vec<MessageVerify> = Vec::new();
+    let mut invalid: Vec<MessageVerify> = Vec::new();
+    for (msg, signature) in msgs.into_iter().zip(signatures.iter()) {
+        if verify_ed25519(
+            &msg.validator,
+            &attestation_payload(id, &msg.message),
+            &signature.0,
+        ) {
+            valid.push(msg);
+        } else {
+            invalid.push(msg);
+        }
+    }
+    (valid, invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cross_chain::{Content, Message, SQOS};
+    use ed25519_dalek::{Keypair, SecretKey, Signer};
+
+    fn fixed_keypair(seed: u8) -> Keypair {
+        let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+        let public = (&secret).into();
+        Keypair { secret, public }
+    }
+
+    fn public_key(keypair: &Keypair) -> PublicKey {
+        let mut data = vec![0u8];
+        data.extend(keypair.public.as_bytes());
+        PublicKey::try_from(data).unwrap()
+    }
+
+    fn message(data: &str) -> Message {
+        Message {
+            from_chain: "OTHER_CHAIN".to_string(),
+            to_chain: "NEAR_CHAIN".to_string(),
+            sender: "OTHER_CHAIN_LOCKER".to_string(),
+            signer: "OTHER_CHAIN_CALLER".to_string(),
+            sqos: SQOS { reveal: false },
+            content: Content {
+                contract: "ft.shanks.testnet".to_string(),
+                action: "ft_balance_of".to_string(),
+                data: data.to_string(),
+            },
+        }
+    }
+
+    fn sign(keypair: &Keypair, id: u64, message: &Message) -> Base64VecU8 {
+        Base64VecU8(
+            keypair
+                .sign(&attestation_payload(id, message))
+                .to_bytes()
+                .to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        let keypair = fixed_keypair(1);
+        let signature = keypair.sign(b"message").to_bytes();
+        assert!(verify_ed25519(
+            &public_key(&keypair),
+            b"message",
+            &signature
+        ));
+        assert!(!verify_ed25519(
+            &public_key(&keypair),
+            b"massage",
+            &signature
+        ));
+        assert!(!verify_ed25519(
+            &public_key(&fixed_keypair(2)),
+            b"message",
+            &signature
+        ));
+        assert!(!verify_ed25519(
+            &public_key(&keypair),
+            b"message",
+            &signature[..63]
+        ));
+    }
+
+    #[test]
+    fn test_verify_ed25519_rejects_other_curves() {
+        let secp256k1: PublicKey = "secp256k1:qMoRgcoXai4mBPsdbHi1wfyxF9TdbPCF4qSDQTRP3TfescSRoUdSx6nmeQoN3aiwGzwMyGXAb1gUjBTv5AY8DXj"
+            .parse()
+            .unwrap();
+        assert!(!verify_ed25519(&secp256k1, b"message", &[0u8; 64]));
+    }
+
+    #[test]
+    fn test_verify_submissions() {
+        let (keypair_1, keypair_2, keypair_3) =
+            (fixed_keypair(1), fixed_keypair(2), fixed_keypair(3));
+        let msg = message("{\"account_id\": \"shanks.testnet\"}");
+        let msgs = vec![
+            MessageVerify {
+                validator: public_key(&keypair_1),
+                message: msg.clone(),
+            },
+            // signed by another key
+            MessageVerify {
+                validator: public_key(&keypair_2),
+                message: msg.clone(),
+            },
+            // signed for another id
+            MessageVerify {
+                validator: public_key(&keypair_3),
+                message: msg.clone(),
+            },
+        ];
+        let signatures = vec![
+            sign(&keypair_1, 7, &msg),
+            sign(&keypair_1, 7, &msg),
+            sign(&keypair_3, 8, &msg),
+        ];
+        let (valid, invalid) = verify_submissions(msgs, &signatures, 7);
+        assert_eq!(1, valid.len());
+        assert_eq!(public_key(&keypair_1), valid[0].validator);
+        assert_eq!(2, invalid.len());
+        assert_eq!(public_key(&keypair_2), invalid[0].validator);
+        assert_eq!(public_key(&keypair_3), invalid[1].validator);
+    }
+
+    #[test]
+    fn test_signature_binds_message() {
+        let keypair = fixed_keypair(1);
+        let msg = message("{\"account_id\": \"shanks.testnet\"}");
+        let other = message("{\"account_id\": \"other_account\"}");
+        let msgs = vec![MessageVerify {
+            validator: public_key(&keypair),
+            message: other,
+        }];
+        let (valid, invalid) = verify_submissions(msgs, &[sign(&keypair, 7, &msg)], 7);
+        assert!(valid.is_empty());
+        assert_eq!(1, invalid.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "one signature is required for each copy")]
+    fn test_missing_signature() {
+        let keypair = fixed_keypair(1);
+        let msgs = vec![MessageVerify {
+            validator: public_key(&keypair),
+            message: message(""),
+        }];
+        verify_submissions(msgs, &[], 7);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::*;
+use node_evaluation::{NodeEvaluation, NodeLiveness, PenaltyCurve};
+
+// The interface of `node_evaluation contract`, served by this contract when built with `single-contract`
+#[near_bindgen]
+impl Contract {
+    /// @notice Initialize the state of `node_evaluation`, see `node_evaluation::Contract::inite`.
+    ///
+    /// @dev `vc_contract_id` must be this contract, `init` must have been called with it as `node_eva_addr`.
+    #[private]
+    pub fn inite(
+        &mut self,
+        cross_contract_id: AccountId,
+        vc_contract_id: AccountId,
+        initial_credibility_value: u32,
+        max_trustworthy_ratio: u32,
+        min_trustworthy_ratio: u32,
+        min_seleted_threshold: u32,
+        trustworthy_threshold: u32,
+    ) {
+        require!(self.evaluation.is_none(), "evaluation already initialized");
+        require!(
+            vc_contract_id == env::current_account_id(),
+            "vc_contract_id must be this contract"
+        );
+        self.evaluation = Some(node_evaluation::Contract::new(
+            cross_contract_id,
+            vc_contract_id,
+            initial_credibility_value,
+            max_trustworthy_ratio,
+            min_trustworthy_ratio,
+            min_seleted_threshold,
+            trustworthy_threshold,
+        ));
+    }
+
+    pub fn get_bls_public_key(&self, pk: PublicKey) -> Option<Base64VecU8> {
+        self.evaluation().get_bls_public_key(pk)
+    }
+
+    pub fn get_current_bls_committee(&self) -> Option<BlsCommittee> {
+        self.evaluation().get_current_bls_committee()
+    }
+
+    pub fn is_banned(&self, pk: PublicKey) -> bool {
+        self.evaluation().is_banned(pk)
+    }
+
+    #[private]
+    pub fn set_penalty_curve(&mut self, curve: PenaltyCurve) {
+        self.evaluation_mut().set_penalty_curve(curve)
+    }
+
+    pub fn get_penalty_curve(&self) -> PenaltyCurve {
+        self.evaluation().get_penalty_curve()
+    }
+
+    #[private]
+    pub fn set_heartbeat_window(&mut self, window: u64) {
+        self.evaluation_mut().set_heartbeat_window(window)
+    }
+
+    pub fn get_liveness(&self, from_index: u64, limit: u64) -> Vec<NodeLiveness> {
+        self.evaluation().get_liveness(from_index, limit)
+    }
+
+    #[private]
+    pub fn set_liveness_step(&mut self, value: u32) {
+        self.evaluation_mut().set_liveness_step(value)
+    }
+
+    pub fn get_consecutive_misses(&self, pk: PublicKey) -> u32 {
+        self.evaluation().get_consecutive_misses(pk)
+    }
+
+    pub fn get_current_epoch(&self) -> u64 {
+        self.evaluation().get_current_epoch()
+    }
+
+    #[private]
+    pub fn set_epoch_length(&mut self, blocks: u64) {
+        self.evaluation_mut().set_epoch_length(blocks)
+    }
+
+    pub fn get_epoch_length(&self) -> u64 {
+        self.evaluation().get_epoch_length()
+    }
+
+    pub fn get_validator_set(&self, epoch: u64) -> Option<ValidatorSet> {
+        self.evaluation().get_validator_set(epoch)
+    }
+
+    pub fn get_current_validator_set(&self) -> Option<ValidatorSet> {
+        self.evaluation().get_current_validator_set()
+    }
+
+    pub fn is_selected(&self, pk: PublicKey, epoch: u64) -> bool {
+        self.evaluation().is_selected(pk, epoch)
+    }
+
+    #[private]
+    pub fn reload_validators_callback(&mut self, epoch: u64) {
+        self.evaluation_mut().reload_validators_callback(epoch)
+    }
+
+    pub fn get_node(&self, from_index: u64, limit: u64) -> Vec<NodeCredibility> {
+        self.evaluation().get_node(from_index, limit)
+    }
+}
+
+#[near_bindgen]
+impl NodeEvaluation for Contract {
+    fn select_validators(&mut self) -> Promise {
+        self.evaluation_mut().select_validators()
+    }
+
+    fn update_nodes(
+        &mut self,
+        trusted: Vec<PublicKey>,
+        untrusted: Vec<PublicKey>,
+        exeception: Vec<(Vec<PublicKey>, u32)>,
+        absent: Vec<PublicKey>,
+        equivocators: Vec<PublicKey>,
+    ) {
+        self.evaluation_mut()
+            .update_nodes(trusted, untrusted, exeception, absent, equivocators)
+    }
+
+    fn update_nodes_batch(&mut self, behaviors: Vec<NodeBehavior>) {
+        self.evaluation_mut().update_nodes_batch(behaviors)
+    }
+
+    fn punish_fraud(&mut self, trusted: Vec<NodeCredibility>, challenger: PublicKey) {
+        self.evaluation_mut().punish_fraud(trusted, challenger)
+    }
+
+    fn get_nodes_credibility(&self, nodes: Vec<PublicKey>) -> Vec<NodeCredibility> {
+        self.evaluation().get_nodes_credibility(nodes)
+    }
+
+    fn get_nodes_operator(&self, nodes: Vec<PublicKey>) -> Vec<Option<AccountId>> {
+        self.evaluation().get_nodes_operator(nodes)
+    }
+
+    fn register_node(&mut self) {
+        self.evaluation_mut().register_node()
+    }
+
+    fn register_bls_key(&mut self, public_key: Base64VecU8, proof_of_possession: Base64VecU8) {
+        self.evaluation_mut()
+            .register_bls_key(public_key, proof_of_possession)
+    }
+
+    fn heartbeat(&mut self, version: String) {
+        self.evaluation_mut().heartbeat(version)
+    }
+
+    fn unregister_node(&mut self) {
+        self.evaluation_mut().unregister_node()
+    }
+
+    #[private]
+    fn set_initial_credibility(&mut self, value: u32) {
+        self.evaluation_mut().set_initial_credibility(value)
+    }
+
+    #[private]
+    fn update_storage_date(&mut self, pk: PublicKey, value: u32) {
+        self.evaluation_mut().update_storage_date(pk, value)
+    }
+}
+
+impl Contract {
+    fn evaluation(&self) -> &node_evaluation::Contract {
+        self.evaluation
+            .as_ref()
+            .unwrap_or_else(|| env::panic_str("evaluation not initialized"))
+    }
+
+    fn evaluation_mut(&mut self) -> &mut node_evaluation::Contract {
+        self.evaluation
+            .as_mut()
+            .unwrap_or_else(|| env::panic_str("evaluation not initialized"))
+    }
+
+    /// Update the credibility right away, no gas is reserved for a promise
+    pub(crate) fn send_scoring(&mut self, scoring: Scoring, _reserved: Gas) {
+        self.report_behavior(scoring, GAS_FOR_UPDATE_NODES);
+    }
+
+    pub(crate) fn send_scorings(&mut self, scorings: Vec<Scoring>, _reserved: Gas) {
+        let behaviors: Vec<NodeBehavior> = scorings
+            .into_iter()
+            .filter(|scoring| !scoring.is_empty())
+            .map(Scoring::into_behavior)
+            .collect();
+        if !behaviors.is_empty() {
+            self.evaluation_mut().record_behaviors(behaviors);
+        }
+    }
+
+    pub(crate) fn report_behavior(&mut self, scoring: Scoring, _gas: Gas) {
+        self.evaluation_mut().record_behavior(
+            scoring.trusted,
+            scoring.untrusted,
+            scoring.exeception,
+            scoring.absent,
+            scoring.equivocators,
+        );
+    }
+
+    pub(crate) fn report_fraud(&mut self, trusted: Vec<NodeCredibility>, challenger: PublicKey) {
+        self.evaluation_mut().record_fraud(trusted, challenger);
+    }
+
+    /// The evaluation of `keys` read from the state of `node_evaluation`, never requested
+    pub(crate) fn local_evaluation(&self, keys: &[PublicKey]) -> Option<Evaluation> {
+        let evaluation = self.evaluation();
+        let operators = evaluation
+            .get_nodes_operator(keys.to_vec())
+            .into_iter()
+            .zip(keys.iter())
+            .filter_map(|(operator, pk)| operator.map(|op| (pk.clone(), op)))
+            .collect();
+        Some(Evaluation {
+            credibility: Some(evaluation.get_nodes_credibility(keys.to_vec())),
+            validator_set: evaluation.get_current_validator_set(),
+            operators: Some(operators),
+        })
+    }
+}
@@ -0,0 +1,191 @@
+use crate::*;
+
+// The validator set of an epoch pushed by `node_evaluation contract` when it is selected
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct CredibilitySnapshot {
+    /// the credibility of each validator when the set was selected
+    pub validator_set: ValidatorSet,
+    /// in the order of `validator_set.validators`, the account which registered the validator
+    pub operators: Vec<Option<AccountId>>,
+    /// the block height the snapshot was received
+    pub block_height: u64,
+    /// validators penalized by this contract since the snapshot was received, their credibility is read again
+    pub penalized: Vec<PublicKey>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// @notice Called from `node_evaluation contract` with the validator set it selected.
+    ///
+    /// @dev A snapshot of an older epoch than the cached one is ignored.
+    pub fn set_credibility_snapshot(
+        &mut self,
+        validator_set: ValidatorSet,
+        operators: Vec<Option<AccountId>>,
+    ) {
+        assert_eq!(env::predecessor_account_id(), self.node_ev_address);
+        require!(
+            operators.len() == validator_set.validators.len(),
+            "operators mismatch"
+        );
+        if let Some(cached) = self.credibility_snapshot.get() {
+            if cached.validator_set.epoch >= validator_set.epoch {
+                log!("snapshot of epoch {} ignored", validator_set.epoch);
+                return;
+            }
+        }
+        self.credibility_snapshot.set(&CredibilitySnapshot {
+            validator_set,
+            operators,
+            block_height: env::block_height(),
+            penalized: Vec::new(),
+        });
+    }
+
+    pub fn get_credibility_snapshot(&self) -> Option<CredibilitySnapshot> {
+        self.credibility_snapshot.get()
+    }
+
+    /// set the number of blocks the snapshot is used by `msg_verify` instead of reading `node_evaluation contract`, `None` to always read it
+    #[private]
+    pub fn set_snapshot_max_age(&mut self, max_age: Option<u64>) {
+        self.snapshot_max_age = max_age;
+    }
+
+    pub fn get_snapshot_max_age(&self) -> Option<u64> {
+        self.snapshot_max_age
+    }
+}
+
+impl Contract {
+    /// The evaluation of `keys` by the snapshot, `None` if the cache is disabled, empty or stale,
+    /// or if any of `keys` was penalized since the snapshot was received.
+    /// Validators outside the snapshot have no credibility, they are non-members of its validator set.
+    #[cfg(not(feature = "single-contract"))]
+    pub(crate) fn cached_evaluation(&self, keys: &[PublicKey]) -> Option<Evaluation> {
+        let max_age = self.snapshot_max_age?;
+        let snapshot = self.credibility_snapshot.get()?;
+        if env::block_height() > snapshot.block_height + max_age {
+            log!(
+                "snapshot of epoch {} is stale",
+                snapshot.validator_set.epoch
+            );
+            return None;
+        }
+        if keys.iter().any(|pk| snapshot.penalized.contains(pk)) {
+            log!(
+                "snapshot of epoch {} is stale for penalized validators",
+                snapshot.validator_set.epoch
+            );
+            return None;
+        }
+        let credibility = keys
+            .iter()
+            .map(|pk| NodeCredibility {
+                validator: pk.clone(),
+                credibility_value: snapshot
+                    .validator_set
+                    .validators
+                    .iter()
+                    .find(|vc| vc.validator == *pk)
+                    .map_or(0, |vc| vc.credibility_value),
+            })
+            .collect();
+        let operators = snapshot
+            .validator_set
+            .validators
+            .iter()
+            .zip(snapshot.operators)
+            .filter_map(|(vc, operator)| operator.map(|op| (vc.validator.clone(), op)))
+            .collect();
+        Some(Evaluation {
+            credibility: Some(credibility),
+            validator_set: Some(snapshot.validator_set),
+            operators: Some(operators),
+        })
+    }
+}
+
+#[cfg(not(feature = "single-contract"))]
+impl Contract {
+    /// Mark the validators penalized since the snapshot was received, equivocators are banned so they leave its validator set
+    pub(crate) fn patch_snapshot(&mut self, penalized: &[PublicKey], banned: &[PublicKey]) {
+        if penalized.is_empty() && banned.is_empty() {
+            return;
+        }
+        let mut snapshot = match self.credibility_snapshot.get() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        let mut changed = false;
+        for pk in penalized.iter() {
+            if snapshot.validator_set.contains(pk) && !snapshot.penalized.contains(pk) {
+                snapshot.penalized.push(pk.clone());
+                changed = true;
+            }
+        }
+        if banned.iter().any(|pk| snapshot.validator_set.contains(pk)) {
+            let (validators, operators) = snapshot
+                .validator_set
+                .validators
+                .into_iter()
+                .zip(snapshot.operators)
+                .filter(|(vc, _)| !banned.contains(&vc.validator))
+                .unzip();
+            snapshot.validator_set.validators = validators;
+            snapshot.operators = operators;
+            changed = true;
+        }
+        if changed {
+            self.credibility_snapshot.set(&snapshot);
+        }
+    }
+
+    /// Drop the snapshot if `epoch` read from `node_evaluation contract` is newer, its push did not arrive.
+    pub(crate) fn observe_epoch(&mut self, epoch: u64) {
+        if let Some(snapshot) = self.credibility_snapshot.get() {
+            if epoch > snapshot.validator_set.epoch {
+                log!(
+                    "snapshot of epoch {} dropped, epoch {} not pushed",
+                    snapshot.validator_set.epoch,
+                    epoch
+                );
+                self.credibility_snapshot.remove();
+            }
+        }
+    }
+
+    /// Update the snapshot by an evaluation read from `node_evaluation contract`,
+    /// the credibility of penalized validators is refreshed, see `observe_epoch` for the epoch.
+    pub(crate) fn refresh_snapshot(&mut self, evaluation: &Evaluation) {
+        if let Some(validator_set) = evaluation.validator_set.as_ref() {
+            self.observe_epoch(validator_set.epoch);
+        }
+        let mut snapshot = match self.credibility_snapshot.get() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        let credibility = match evaluation.credibility.as_ref() {
+            Some(credibility) => credibility,
+            None => return,
+        };
+        let penalized = snapshot.penalized.len();
+        for nc in credibility.iter() {
+            if let Some(index) = snapshot.penalized.iter().position(|pk| *pk == nc.validator) {
+                snapshot.penalized.swap_remove(index);
+                if let Some(vc) = snapshot
+                    .validator_set
+                    .validators
+                    .iter_mut()
+                    .find(|vc| vc.validator == nc.validator)
+                {
+                    vc.credibility_value = nc.credibility_value;
+                }
+            }
+        }
+        if snapshot.penalized.len() != penalized {
+            self.credibility_snapshot.set(&snapshot);
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{AccountId, PublicKey};
+use node_evaluation::credibility_weight;
+
+const PRECISION: u128 = 10_000;
+
+// How the copy of a validator weighs in the aggregation
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum VotingPower {
+    /// the credibility of the validator
+    Linear,
+    /// the square root of the credibility of the validator scaled by 100, so a few top nodes weigh less
+    SquareRoot,
+    /// the credibility of the validator, at most `cap` [0~10000] of the credibility of every copy
+    Capped { cap: u32 },
+    /// the credibility of the validator times its bond in yoctoNEAR, see `bond`
+    StakeWeighted,
+    /// one vote for each distinct operator in a group, validators without a known operator vote on their own
+    PerOperator,
+    /// one vote for each validator
+    Count,
+}
+
+impl VotingPower {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            VotingPower::Capped { cap } => *cap > 0 && *cap as u128 <= PRECISION,
+            _ => true,
+        }
+    }
+}
+
+// The copy of one validator
+#[derive(Clone)]
+pub struct Vote {
+    pub validator: PublicKey,
+    pub message_hash: String,
+    pub credibility: u32,
+    pub stake: u128,
+    pub operator: Option<AccountId>,
+}
+
+// Validators who submitted identical copies
+#[derive(Clone, PartialEq, Debug)]
+pub struct GroupTally {
+    pub message_hash: String,
+    pub validators: Vec<PublicKey>,
+    pub credibility: u64,
+    pub power: u128,
+    /// [0~10000], power of the group over the total power
+    pub weight: u32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tally {
+    /// sorted by weight, then by the number of validators, then by message hash, the leading group first
+    pub groups: Vec<GroupTally>,
+    pub total_credibility: u64,
+    pub total_power: u128,
+}
+
+/// Group the votes by message hash and weigh each group by `voting_power`.
+/// A validator is counted once in a group however many identical copies it submitted.
+pub fn tally(votes: &[Vote], voting_power: &VotingPower) -> Tally {
+    let mut groups: Vec<(GroupTally, Vec<Option<AccountId>>)> = Vec::new();
+    let mut counted: Vec<&Vote> = Vec::new();
+    for vote in votes.iter() {
+        if counted
+            .iter()
+            .any(|v| v.validator == vote.validator && v.message_hash == vote.message_hash)
+        {
+            continue;
+        }
+        counted.push(vote);
+    }
+    let total_credibility: u64 = counted.iter().map(|v| v.credibility as u64).sum();
+    for vote in counted {
+        let position = match groups
+            .iter()
+            .position(|(group, _)| group.message_hash == vote.message_hash)
+        {
+            Some(position) => position,
+            None => {
+                groups.push((
+                    GroupTally {
+                        message_hash: vote.message_hash.clone(),
+                        validators: Vec::new(),
+                        credibility: 0,
+                        power: 0,
+                        weight: 0,
+                    },
+                    Vec::new(),
+                ));
+                groups.len() - 1
+            }
+        };
+        let (group, operators) = &mut groups[position];
+        group.validators.push(vote.validator.clone());
+        group.credibility += vote.credibility as u64;
+        let power = match voting_power {
+            VotingPower::Linear => vote.credibility as u128,
+            VotingPower::SquareRoot => isqrt(vote.credibility as u128 * PRECISION),
+            VotingPower::Capped { cap } => std::cmp::min(
+                vote.credibility as u128,
+                total_credibility as u128 * *cap as u128 / PRECISION,
+            ),
+            VotingPower::StakeWeighted => (vote.credibility as u128).saturating_mul(vote.stake),
+            VotingPower::Count => 1,
+            VotingPower::PerOperator => match &vote.operator {
+                Some(operator) if operators.contains(&Some(operator.clone())) => 0,
+                operator => {
+                    operators.push(operator.clone());
+                    1
+                }
+            },
+        };
+        group.power = group.power.saturating_add(power);
+    }
+    let total_power: u128 = groups
+        .iter()
+        .fold(0, |total, (group, _)| total.saturating_add(group.power));
+    let mut groups: Vec<GroupTally> = groups
+        .into_iter()
+        .map(|(mut group, _)| {
+            group.weight = credibility_weight(group.power, total_power);
+            group
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.weight
+            .cmp(&a.weight)
+            .then(b.validators.len().cmp(&a.validators.len()))
+            .then(a.message_hash.cmp(&b.message_hash))
+    });
+    Tally {
+        groups,
+        total_credibility,
+        total_power,
+    }
+}
+
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = value / 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn validator(seed: u8) -> PublicKey {
+        let mut data = vec![0u8];
+        data.extend([seed; 32]);
+        PublicKey::try_from(data).unwrap()
+    }
+
+    fn vote(seed: u8, message_hash: &str, credibility: u32) -> Vote {
+        Vote {
+            validator: validator(seed),
+            message_hash: message_hash.to_string(),
+            credibility,
+            stake: 0,
+            operator: None,
+        }
+    }
+
+    fn weights(tally: &Tally) -> Vec<(String, u32)> {
+        tally
+            .groups
+            .iter()
+            .map(|group| (group.message_hash.clone(), group.weight))
+            .collect()
+    }
+
+    #[test]
+    fn test_linear() {
+        let votes = vec![
+            vote(1, "b", 1000),
+            vote(2, "a", 4000),
+            vote(3, "b", 2000),
+            vote(3, "b", 2000),
+            vote(4, "a", 2000),
+        ];
+        let tally = tally(&votes, &VotingPower::Linear);
+        assert_eq!(9000, tally.total_credibility);
+        assert_eq!(9000, tally.total_power);
+        assert_eq!(
+            vec![("a".to_string(), 6666), ("b".to_string(), 3333)],
+            weights(&tally)
+        );
+        assert_eq!(vec![validator(2), validator(4)], tally.groups[0].validators);
+        assert_eq!(6000, tally.groups[0].credibility);
+    }
+
+    #[test]
+    fn test_square_root() {
+        let votes = vec![vote(1, "a", 9000), vote(2, "b", 1000), vote(3, "b", 1000)];
+        assert_eq!(
+            vec![("a".to_string(), 8181), ("b".to_string(), 1818)],
+            weights(&tally(&votes, &VotingPower::Linear))
+        );
+        let tally = tally(&votes, &VotingPower::SquareRoot);
+        assert_eq!(9486 + 3162 * 2, tally.total_power);
+        assert_eq!(
+            vec![("a".to_string(), 6000), ("b".to_string(), 4000)],
+            weights(&tally)
+        );
+        assert_eq!(0, isqrt(0));
+        assert_eq!(100, isqrt(10000));
+        assert_eq!(99, isqrt(9999));
+    }
+
+    #[test]
+    fn test_capped() {
+        let votes = vec![vote(1, "a", 8000), vote(2, "b", 1000), vote(3, "b", 1000)];
+        let tally = tally(&votes, &VotingPower::Capped { cap: 2500 });
+        // the top node weighs at most 2500 of the 10000 credibility
+        assert_eq!(2500 + 2000, tally.total_power);
+        assert_eq!(
+            vec![("a".to_string(), 5555), ("b".to_string(), 4444)],
+            weights(&tally)
+        );
+        assert!(!VotingPower::Capped { cap: 0 }.is_valid());
+        assert!(!VotingPower::Capped { cap: 10001 }.is_valid());
+    }
+
+    #[test]
+    fn test_stake_weighted() {
+        let mut votes = vec![vote(1, "a", 4000), vote(2, "b", 4000), vote(3, "b", 2000)];
+        votes[0].stake = 3;
+        votes[1].stake = 1;
+        votes[2].stake = 1;
+        let tally = tally(&votes, &VotingPower::StakeWeighted);
+        assert_eq!(18000, tally.total_power);
+        assert_eq!(
+            vec![("a".to_string(), 6666), ("b".to_string(), 3333)],
+            weights(&tally)
+        );
+        // validators without a bond have no power
+        votes[0].stake = 0;
+        assert_eq!(
+            vec![("b".to_string(), 10000), ("a".to_string(), 0)],
+            weights(&super::tally(&votes, &VotingPower::StakeWeighted))
+        );
+        // bonds of millions of NEAR in yoctoNEAR are weighed without overflow
+        votes[0].stake = 3 * 10u128.pow(31);
+        votes[1].stake = 10u128.pow(31);
+        votes[2].stake = 10u128.pow(31);
+        assert_eq!(
+            vec![("a".to_string(), 6666), ("b".to_string(), 3333)],
+            weights(&super::tally(&votes, &VotingPower::StakeWeighted))
+        );
+        votes[0].stake = u128::MAX;
+        assert_eq!(
+            u128::MAX,
+            super::tally(&votes, &VotingPower::StakeWeighted).total_power
+        );
+    }
+
+    #[test]
+    fn test_per_operator() {
+        let operator = |name: &str| Some(AccountId::new_unchecked(name.to_string()));
+        let mut votes = vec![
+            vote(1, "a", 4000),
+            vote(2, "a", 4000),
+            vote(3, "a", 4000),
+            vote(4, "b", 1000),
+            vote(5, "b", 1000),
+        ];
+        votes[0].operator = operator("alice");
+        votes[1].operator = operator("alice");
+        votes[2].operator = operator("alice");
+        votes[3].operator = operator("bob");
+        votes[4].operator = None;
+        let tally = tally(&votes, &VotingPower::PerOperator);
+        assert_eq!(3, tally.total_power);
+        assert_eq!(
+            vec![("b".to_string(), 6666), ("a".to_string(), 3333)],
+            weights(&tally)
+        );
+        assert_eq!(12000, tally.groups[1].credibility);
+    }
+
+    #[test]
+    fn test_count() {
+        let votes = vec![vote(1, "a", 0), vote(2, "b", 0), vote(3, "b", 0)];
+        assert_eq!(0, tally(&votes, &VotingPower::Linear).total_power);
+        let tally = tally(&votes, &VotingPower::Count);
+        assert_eq!(3, tally.total_power);
+        assert_eq!(0, tally.total_credibility);
+        assert_eq!(
+            vec![("b".to_string(), 6666), ("a".to_string(), 3333)],
+            weights(&tally)
+        );
+    }
+
+    #[test]
+    fn test_tie_break() {
+        let mut votes = vec![
+            vote(1, "c", 2000),
+            vote(2, "b", 1000),
+            vote(3, "b", 1000),
+            vote(4, "a", 2000),
+        ];
+        let order = |votes: &[Vote]| -> Vec<String> {
+            tally(votes, &VotingPower::Linear)
+                .groups
+                .into_iter()
+                .map(|group| group.message_hash)
+                .collect()
+        };
+        // equal weights, more validators first, then the lower message hash
+        assert_eq!(vec!["b", "a", "c"], order(&votes));
+        // whatever the order of the votes
+        votes.reverse();
+        assert_eq!(vec!["b", "a", "c"], order(&votes));
+    }
+
+    #[test]
+    fn test_no_power() {
+        let votes = vec![vote(1, "a", 0), vote(2, "b", 0)];
+        let tally = tally(&votes, &VotingPower::Linear);
+        assert_eq!(0, tally.total_power);
+        assert_eq!(
+            vec![("a".to_string(), 0), ("b".to_string(), 0)],
+            weights(&tally)
+        );
+        assert!(super::tally(&[], &VotingPower::Linear).groups.is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+//! BLS signatures over BLS12-381 in the minimal-pubkey-size variant with proof of possession,
+//! public keys in G1 (48 bytes compressed), signatures in G2 (96 bytes compressed).
+//! Hashing to G2 follows `BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_`.
+
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{
+    multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, G2Projective, Gt,
+};
+use std::convert::TryFrom;
+
+pub const PUBLIC_KEY_LENGTH: usize = 48;
+pub const SIGNATURE_LENGTH: usize = 96;
+/// Domain separation tag of message signatures
+pub const DST_SIGNATURE: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// Domain separation tag of proofs of possession
+pub const DST_POP: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Decode a compressed G1 public key, rejecting the identity and points outside the subgroup.
+pub fn public_key_from_bytes(bytes: &[u8]) -> Option<G1Affine> {
+    let bytes = <&[u8; PUBLIC_KEY_LENGTH]>::try_from(bytes).ok()?;
+    let public_key = Option::<G1Affine>::from(G1Affine::from_compressed(bytes))?;
+    if bool::from(public_key.is_identity()) {
+        return None;
+    }
+    Some(public_key)
+}
+
+/// Decode a compressed G2 signature, rejecting points outside the subgroup.
+pub fn signature_from_bytes(bytes: &[u8]) -> Option<G2Affine> {
+    let bytes = <&[u8; SIGNATURE_LENGTH]>::try_from(bytes).ok()?;
+    Option::<G2Affine>::from(G2Affine::from_compressed(bytes))
+}
+
+pub fn hash_to_g2(message: &[u8], dst: &[u8]) -> G2Affine {
+    G2Affine::from(
+        <G2Projective as HashToCurve<ExpandMsgXmd<sha2::Sha256>>>::hash_to_curve(message, dst),
+    )
+}
+
+/// Check e(public_key, H(message)) == e(g1, signature) with a single final exponentiation.
+fn core_verify(public_key: &G1Affine, message: &[u8], signature: &G2Affine, dst: &[u8]) -> bool {
+    let hash = G2Prepared::from(hash_to_g2(message, dst));
+    let signature = G2Prepared::from(*signature);
+    let neg_generator = -G1Affine::generator();
+    multi_miller_loop(&[(public_key, &hash), (&neg_generator, &signature)]).final_exponentiation()
+        == Gt::identity()
+}
+
+pub fn verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    match (
+        public_key_from_bytes(public_key),
+        signature_from_bytes(signature),
+    ) {
+        (Some(public_key), Some(signature)) => {
+            core_verify(&public_key, message, &signature, DST_SIGNATURE)
+        }
+        _ => false,
+    }
+}
+
+/// The message signed by the proof of possession of `public_key`, its compressed bytes followed by the bytes of `owner`,
+/// the NEAR public key of the node registering it
+pub fn proof_of_possession_payload(public_key: &[u8], owner: &[u8]) -> Vec<u8> {
+    [public_key, owner].concat()
+}
+
+/// Check the signature of `public_key` over `proof_of_possession_payload(public_key, owner)`.
+/// It prevents rogue key attacks on aggregation, and the proof of one node can not be replayed by another.
+pub fn verify_proof_of_possession(public_key: &[u8], owner: &[u8], proof: &[u8]) -> bool {
+    match (
+        public_key_from_bytes(public_key),
+        signature_from_bytes(proof),
+    ) {
+        (Some(pk), Some(proof)) => core_verify(
+            &pk,
+            &proof_of_possession_payload(public_key, owner),
+            &proof,
+            DST_POP,
+        ),
+        _ => false,
+    }
+}
+
+/// Sum of the public keys, `None` if any of them is invalid or the list is empty.
+pub fn aggregate_public_keys(public_keys: &[&[u8]]) -> Option<G1Affine> {
+    if public_keys.is_empty() {
+        return None;
+    }
+    let mut aggregate = G1Projective::identity();
+    for public_key in public_keys {
+        aggregate += public_key_from_bytes(public_key)?;
+    }
+    Some(G1Affine::from(aggregate))
+}
+
+/// Verify an aggregate signature of one message by all of `public_keys`.
+/// Every key must have been registered with a valid proof of possession.
+pub fn fast_aggregate_verify(public_keys: &[&[u8]], message: &[u8], signature: &[u8]) -> bool {
+    match (
+        aggregate_public_keys(public_keys),
+        signature_from_bytes(signature),
+    ) {
+        (Some(aggregate), Some(signature)) => {
+            core_verify(&aggregate, message, &signature, DST_SIGNATURE)
+        }
+        _ => false,
+    }
+}
+
+/// Indexes of the set bits of `bitmap`, bit `i` is `bitmap[i / 8] >> (i % 8) & 1`.
+/// `None` if the bitmap is not exactly `ceil(len / 8)` bytes or sets a bit at or above `len`.
+pub fn bitmap_indexes(bitmap: &[u8], len: usize) -> Option<Vec<usize>> {
+    // exactly the bytes needed for `len` bits
+    if bitmap.len() * 8 < len || bitmap.len() * 8 >= len + 8 {
+        return None;
+    }
+    let mut indexes = Vec::new();
+    for (byte_index, byte) in bitmap.iter().enumerate() {
+        for bit in 0..8 {
+            if byte >> bit & 1 == 1 {
+                let index = byte_index * 8 + bit;
+                if index >= len {
+                    return None;
+                }
+                indexes.push(index);
+            }
+        }
+    }
+    Some(indexes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bls12_381::Scalar;
+
+    // generated with `blst` (min_pk), secret keys from `key_gen(&[seed; 32], &[])` for seed 1..=3
+    const SECRET_KEYS: [&str; 3] = [
+        "144b27828e305a2d67fc7f4eea6de706b405cdd1ab8ad2daec046ccdeeec8b79",
+        "1ff56eef5220c383a6522aa9a92776e3034bf1153839d54c9e3d2bcb6c04948e",
+        "70af5b11c1e57ab1ad314bf7178e5298a53d39922592216a21990e7e1293d0e2",
+    ];
+    const PUBLIC_KEYS: [&str; 3] = [
+        "95a254501b7733239ed3cec4d56737977bd09ede881d8a234560e83e5525017add3b1dcc3eabfb85e12a4131b19c253b",
+        "ac80a5e08c712d5f08f0306ad743f7d8c215d982489b84a1d6ba805733d94c006e8938f9089a75db3ffa135af33bc69a",
+        "96df714a5cc9ddd2298546dce3d6d3827762a6d5b1c2a91e5ca93c9c898b1b4319cc105c493212a55b63080732ec2249",
+    ];
+    const MESSAGE: &[u8] = b"dante trusted bridge";
+    const SIGNATURES: [&str; 3] = [
+        "93c9efabd05b049222eb22bb2a130b0e523375246f8cefb05dbe0d77e4e813a56c7296b737eba1226fd81fe453576f0c08bd55ff99c1283f6ed0ddc731696ac28c35f15d4d9f21fbe2a0efc25bb8e742e1e6535d2101829c7f9434f31a05f4a5",
+        "a0e699f4efa986b9cae0ecaf4fca23ffb8489c6f43f5b15da06dd15a307c869ffa40a22e45d3935d49c29e641e7871280e69a6ba47c833a9175094bed88cbacec64e163bf49515e6f2048c5e82011bc26e5951ee7d87a6d065009db6d44a0a76",
+        "b6517f0bd7277fd8bebc9c2f8cbe9ac35c8a68dad3c7fb6e9e11e89d6c6dde7b727ebbbddcbb87bb36060ff030a298f40965e350d4fa8292ddad10528d77a4ab8cb35483212dc922f52491d2fd61a95c69b5ee27dd54f8339ef84aa750ca6789",
+    ];
+    const PROOFS_OF_POSSESSION: [&str; 3] = [
+        "846aa12a4402eb67cb92a497e0716db573c817a4163783153f0ddca475f4870200049d8e9ed35087c786059c1f26fc9d0d39e3098f1bae074c062f84f24353210666bd58c0d9be3ff76ba9dd9ce905c5b602a12e78a04350275faacce8b7137d",
+        "b1b22261eeb641b36d4f701f7e5635c5dd0ee53102e7ad8c11594be0d785f0bb5d75bd063ec2caa415e953f85e6e18e110d7ae595d18940e60894bd0a39eb157c1f646ee0f2079d64bd7f4e3c6cbc297e74ce69f3ae4e0728f915f1aac3cdf9b",
+        "958f7ca277b5d44b57008bc90e88d4b8dbc941fd514124c7260176b2199e66e862eaf2e8c6145f4aa95a5362ba10f6a611136e673ec2448619e768f2a978955c3aba6eb2b995c3e1c7851a4945fedc8d75709c4d0a98f6d6c70c5a47e9fdbf26",
+    ];
+    // aggregate of the signatures of key 1, 2 and 3
+    const AGGREGATE_123: &str = "82963c78c136d028e2540d6fa1d5b45dbab13346648589e223ca960d344300808bc492f78900a87f7a86f35b0a392dca174219b145aa2ba70f5fe9a9463f4a0145a42650ecef09da76c3e526d2bd88d96875206e85c6a52ed0848b51ecfb9940";
+    // aggregate of the signatures of key 1 and 3
+    const AGGREGATE_13: &str = "b95e588484f18f3bb822b00c6cf693d8a93f78ca664c6d02ef3b8248ed911c80963c4f0c3334a6e30ea0a2b0e7eee9e408d8702062ccb68eb2c0f6bcbeebea55b24f3b20adba99fbdfe5d2587c0c505b57b5a68c3fa481dea793f46a253373f2";
+
+    fn bytes(value: &str) -> Vec<u8> {
+        hex::decode(value).unwrap()
+    }
+
+    fn public_keys() -> Vec<Vec<u8>> {
+        PUBLIC_KEYS.iter().map(|pk| bytes(pk)).collect()
+    }
+
+    fn secret_key(sk: &str) -> Scalar {
+        // secret keys are big endian, `Scalar` is little endian
+        let mut le = [0u8; 32];
+        le.copy_from_slice(&bytes(sk));
+        le.reverse();
+        Scalar::from_bytes(&le).unwrap()
+    }
+
+    #[test]
+    fn test_public_key_derivation() {
+        for (sk, pk) in SECRET_KEYS.iter().zip(PUBLIC_KEYS.iter()) {
+            let derived = G1Affine::from(G1Affine::generator() * secret_key(sk));
+            assert_eq!(bytes(pk), derived.to_compressed().to_vec());
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        for (pk, sig) in PUBLIC_KEYS.iter().zip(SIGNATURES.iter()) {
+            assert!(verify(&bytes(pk), MESSAGE, &bytes(sig)));
+            assert!(!verify(&bytes(pk), b"dante trusted bridgE", &bytes(sig)));
+        }
+        assert!(!verify(
+            &bytes(PUBLIC_KEYS[0]),
+            MESSAGE,
+            &bytes(SIGNATURES[1])
+        ));
+    }
+
+    #[test]
+    fn test_proof_of_possession() {
+        let owner: &[u8] = &[0u8; 33];
+        let other: &[u8] = &[1u8; 33];
+        for (sk, (pk, pop)) in SECRET_KEYS
+            .iter()
+            .zip(PUBLIC_KEYS.iter().zip(PROOFS_OF_POSSESSION.iter()))
+        {
+            let pk = bytes(pk);
+            let proof = G2Affine::from(
+                hash_to_g2(&proof_of_possession_payload(&pk, owner), DST_POP) * secret_key(sk),
+            )
+            .to_compressed();
+            assert!(verify_proof_of_possession(&pk, owner, &proof));
+            // the proof is bound to the node registering the key
+            assert!(!verify_proof_of_possession(&pk, other, &proof));
+            // so is not the signature of the key over itself
+            assert!(!verify_proof_of_possession(&pk, owner, &bytes(pop)));
+        }
+        // a message signature is not a proof of possession
+        assert!(!verify_proof_of_possession(
+            &bytes(PUBLIC_KEYS[0]),
+            owner,
+            &bytes(SIGNATURES[0])
+        ));
+    }
+
+    #[test]
+    fn test_fast_aggregate_verify() {
+        let public_keys = public_keys();
+        let all: Vec<&[u8]> = public_keys.iter().map(|pk| pk.as_slice()).collect();
+        assert!(fast_aggregate_verify(&all, MESSAGE, &bytes(AGGREGATE_123)));
+        let subset: Vec<&[u8]> = vec![&public_keys[0], &public_keys[2]];
+        assert!(fast_aggregate_verify(
+            &subset,
+            MESSAGE,
+            &bytes(AGGREGATE_13)
+        ));
+
+        // wrong signer set or message
+        assert!(!fast_aggregate_verify(&all, MESSAGE, &bytes(AGGREGATE_13)));
+        assert!(!fast_aggregate_verify(
+            &subset,
+            MESSAGE,
+            &bytes(AGGREGATE_123)
+        ));
+        assert!(!fast_aggregate_verify(
+            &all,
+            b"other",
+            &bytes(AGGREGATE_123)
+        ));
+        assert!(!fast_aggregate_verify(&[], MESSAGE, &bytes(AGGREGATE_123)));
+    }
+
+    #[test]
+    fn test_malformed_inputs() {
+        let pk = bytes(PUBLIC_KEYS[0]);
+        let sig = bytes(SIGNATURES[0]);
+        assert!(public_key_from_bytes(&pk[..47]).is_none());
+        assert!(signature_from_bytes(&sig[..95]).is_none());
+        // compressed identity
+        let mut identity = [0u8; PUBLIC_KEY_LENGTH];
+        identity[0] = 0xc0;
+        assert!(public_key_from_bytes(&identity).is_none());
+        let mut corrupted = pk.clone();
+        corrupted[10] ^= 0xff;
+        assert!(!verify(&corrupted, MESSAGE, &sig));
+        assert!(!verify(&pk, MESSAGE, &[0u8; SIGNATURE_LENGTH]));
+    }
+
+    #[test]
+    fn test_bitmap_indexes() {
+        assert_eq!(Some(vec![]), bitmap_indexes(&[], 0));
+        assert_eq!(Some(vec![0, 2]), bitmap_indexes(&[0b101], 3));
+        assert_eq!(Some(vec![1, 8]), bitmap_indexes(&[0b10, 0b1], 9));
+        // bit out of range
+        assert_eq!(None, bitmap_indexes(&[0b1000], 3));
+        // wrong length
+        assert_eq!(None, bitmap_indexes(&[0b1, 0], 3));
+        assert_eq!(None, bitmap_indexes(&[], 3));
+    }
+}
@@ -1,20 +1,60 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
+use near_sdk::collections::{LookupMap, LookupSet, UnorderedMap};
+use near_sdk::json_types::Base64VecU8;
+#[cfg(not(feature = "embedded"))]
+use near_sdk::near_bindgen;
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, ext_contract, near_bindgen, AccountId, Balance, Gas, PanicOnDefault, PublicKey,
+    env, ext_contract, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseResult, PublicKey,
 };
 // use near_sdk::json_types::{Base58PublicKey};
 
+pub mod bls;
+mod penalty;
+pub use penalty::{correlated_penalty_step, credibility_weight, PenaltyCurve};
+
 const MIN_CONFIDENCE: u32 = 0;
 const MAX_CONFIDENCE: u32 = 10000;
 const MIDDLE_CONFIDENCE: u32 = (MIN_CONFIDENCE + MAX_CONFIDENCE) / 2;
 const RANGE: u32 = MAX_CONFIDENCE - MIN_CONFIDENCE;
 const SUCCESS_STEP: u32 = 100;
 const DO_EVIL_STEP: u32 = 200;
+// step of an untrusted group holding a third of the weight or more, see `PenaltyCurve`
+const MAX_DO_EVIL_STEP: u32 = 1000;
 const EXECEPTION_STEP: u32 = 100;
+const LIVENESS_STEP: u32 = 50;
+const FRAUD_STEP: u32 = 2000;
+const CHALLENGER_REWARD_STEP: u32 = 200;
+// bytes of the client version kept by `heartbeat`
+const MAX_VERSION_LEN: usize = 64;
+// blocks before anyone but this contract or `cross-chain protocol contract` can select the validators again
+const DEFAULT_EPOCH_LENGTH: u64 = 1000;
 // const PRECISION: u32 = 10_000;
 const NO_DEPOSIT: Balance = 0;
+const GAS_FOR_RELOAD_VALIDATORS: Gas = Gas(30_000_000_000_000);
+const GAS_FOR_RELOAD_CALLBACK: Gas = Gas(10_000_000_000_000);
+#[cfg(not(feature = "embedded"))]
+const GAS_FOR_PUSH_SNAPSHOT: Gas = Gas(20_000_000_000_000);
+#[cfg(not(feature = "embedded"))]
+const GAS_FOR_PUSH_CALLBACK: Gas = Gas(5_000_000_000_000);
+
+/// Storage key of a collection, prefixed when built into `msg-verify contract` whose collections use single bytes too
+fn storage_key(key: u8) -> Vec<u8> {
+    if cfg!(feature = "embedded") {
+        vec![b'E', key]
+    } else {
+        vec![key]
+    }
+}
+
+/// `#[private]` is not expanded when the contract is built into `msg-verify contract`
+fn assert_private() {
+    assert_eq!(
+        env::predecessor_account_id(),
+        env::current_account_id(),
+        "EVALUATION: Method is private"
+    );
+}
 
 // For message verification
 #[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug)]
@@ -24,12 +64,103 @@ pub struct NodeCredibility {
     pub credibility_value: u32,
 }
 
+// The behavior of the validators in one verification, see `update_nodes`
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct NodeBehavior {
+    pub trusted: Vec<PublicKey>,
+    pub untrusted: Vec<PublicKey>,
+    pub exeception: Vec<(Vec<PublicKey>, u32)>,
+    pub absent: Vec<PublicKey>,
+    pub equivocators: Vec<PublicKey>,
+}
+
+#[derive(
+    Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ValidatorSetStatus {
+    /// `reload_validators` has been sent but not resolved yet
+    Pending,
+    /// `cross-chain protocol contract` accepted the set
+    Acknowledged,
+    /// `reload_validators` failed, the two contracts disagree on this epoch
+    Failed,
+}
+
+// Snapshot of the validators selected for one epoch
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct ValidatorSet {
+    pub epoch: u64,
+    pub validators: Vec<NodeCredibility>,
+    /// hex encoded sha256 over the borsh serialized `(epoch, validators)`
+    pub commitment: String,
+    pub status: ValidatorSetStatus,
+}
+
+impl ValidatorSet {
+    pub fn new(epoch: u64, validators: Vec<NodeCredibility>) -> Self {
+        let commitment = Self::commit(epoch, &validators);
+        Self {
+            epoch,
+            validators,
+            commitment,
+            status: ValidatorSetStatus::Pending,
+        }
+    }
+
+    pub fn commit(epoch: u64, validators: &[NodeCredibility]) -> String {
+        let data = (epoch, validators).try_to_vec().unwrap();
+        hex::encode(env::sha256(&data))
+    }
+
+    pub fn contains(&self, pk: &PublicKey) -> bool {
+        self.validators.iter().any(|vc| vc.validator == *pk)
+    }
+}
+
+// Last heartbeat reported by an off-chain node
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct Heartbeat {
+    pub block_height: u64,
+    pub version: String,
+}
+
+// For liveness monitoring
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct NodeLiveness {
+    pub validator: PublicKey,
+    pub heartbeat: Option<Heartbeat>,
+    /// blocks since the last heartbeat, `None` if the node never sent one
+    pub staleness: Option<u64>,
+}
+
+// Validator set of the current epoch with the BLS public keys for aggregate-signature verification
+#[derive(Clone, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct BlsCommittee {
+    pub epoch: u64,
+    pub validators: Vec<NodeCredibility>,
+    /// in the order of `validators`, `None` if the validator has no registered BLS key
+    pub bls_public_keys: Vec<Option<Base64VecU8>>,
+    /// in the order of `validators`, the account which registered the validator
+    pub operators: Vec<Option<AccountId>>,
+}
+
 pub trait NodeEvaluation {
     /// @notice Called from cross-chain node for re-selecting nodes for this time stage.
     ///
     /// @dev Refresh the begining and end of the current time stage if the current period ended.
-    /// Cross contract call to `cross-chain protocol contract` to `reload_validators` new nodes
-    fn select_validators(&self);
+    /// Anyone can select the first validator set, then a new one once the current epoch lasted `epoch_length` blocks.
+    /// This contract and `cross-chain protocol contract` can select a new set at any time.
+    /// The set of every epoch is kept unchanged for audits and fraud proofs.
+    /// Cross contract call to `cross-chain protocol contract` to `reload_validators` new nodes.
+    /// The selected set is stored as the snapshot of a new epoch, and its status is resolved by `reload_validators_callback`.
+    /// The snapshot is also pushed to `msg-verify contract` with the operators of the validators.
+    fn select_validators(&mut self) -> Promise;
 
     /// @notice Called from `msg-verify`. Update node credibility by node behaviors after message verification.
     ///
@@ -38,13 +169,32 @@ pub trait NodeEvaluation {
     /// @param trusted, validators delivering the trusted message;
     /// @param untrusted, validators delivering the untrusted message;
     /// @param exeception, validators did not reach any agreement with verification message.
+    /// @param absent, selected validators of the current epoch who did not submit any copy.
+    /// @param equivocators, validators who submitted conflicting copies of one message, they are banned.
     fn update_nodes(
         &mut self,
         trusted: Vec<PublicKey>,
         untrusted: Vec<PublicKey>,
         exeception: Vec<(Vec<PublicKey>, u32)>,
+        absent: Vec<PublicKey>,
+        equivocators: Vec<PublicKey>,
     );
 
+    /// @notice Called from `msg-verify` after a batch verification. Same as `update_nodes` for each verification of the batch.
+    ///
+    /// @dev Each behavior is scored on its own in order, so the penalties of one message never add up with another.
+    ///
+    /// @param behaviors The behavior of the validators in each verification.
+    fn update_nodes_batch(&mut self, behaviors: Vec<NodeBehavior>);
+
+    /// @notice Called from `msg-verify` when a fraud proof against a verified message is accepted.
+    ///
+    /// @dev Reverse the reward of the trusted group, slash it, and reward the challenger if it is a registered node.
+    ///
+    /// @param trusted, validators of the trusted group with their credibility before the verification.
+    /// @param challenger, the node who submitted the fraud proof.
+    fn punish_fraud(&mut self, trusted: Vec<NodeCredibility>, challenger: PublicKey);
+
     /// @notice Called from `msg-verify`. Update node credibility by node behaviors after message verification.
     ///
     /// @dev Use node credibility evaluation algorithm.
@@ -58,17 +208,38 @@ pub trait NodeEvaluation {
     /// @param nodes Validators
     fn get_nodes_credibility(&self, nodes: Vec<PublicKey>) -> Vec<NodeCredibility>;
 
+    /// @notice Called from `msg verify contract` to count the distinct operators of the validators agreeing on a message
+    ///
+    /// @param nodes Validators
+    /// @return The account which registered each node, `None` if the node is not registered.
+    fn get_nodes_operator(&self, nodes: Vec<PublicKey>) -> Vec<Option<AccountId>>;
+
     /// @notice Called from off-chain nodes to register themselves as the cross chain nodes.
     /// Get node address through `env::signer_account_id()`.
     fn register_node(&mut self);
 
+    /// @notice Called from off-chain nodes to register the BLS public key used to sign aggregated messages.
+    /// Get node address through `env::signer_account_pk()`.
+    ///
+    /// @param public_key The compressed G1 public key.
+    /// @param proof_of_possession The signature of the key over itself and the node public key, see `bls::verify_proof_of_possession`.
+    /// A key already registered by another node is rejected.
+    fn register_bls_key(&mut self, public_key: Base64VecU8, proof_of_possession: Base64VecU8);
+
+    /// @notice Called from off-chain nodes periodically to prove they are alive.
+    /// Get node address through `env::signer_account_pk()`.
+    ///
+    /// @param version The client version reported by the node, at most `MAX_VERSION_LEN` bytes.
+    fn heartbeat(&mut self, version: String);
+
     /// @notice Called from off-chain nodes to unregister.
     /// Get node address through `env::signer_account_id()`.
     fn unregister_node(&mut self);
 
-    /// set the value of the credibility of the newly added validator
+    /// set the value of the credibility of the newly added validator, only by this contract
     fn set_initial_credibility(&mut self, value: u32);
 
+    /// set the credibility of validator `pk`, only by this contract
     fn update_storage_date(&mut self, pk: PublicKey, value: u32);
 }
 
@@ -77,7 +248,22 @@ pub trait VerificationContract {
     fn reload_validators(&mut self, validators: Vec<PublicKey>);
 }
 
-#[near_bindgen]
+#[ext_contract(ext_vc)]
+pub trait MsgVerifyContract {
+    fn set_credibility_snapshot(
+        &mut self,
+        validator_set: ValidatorSet,
+        operators: Vec<Option<AccountId>>,
+    );
+}
+
+#[ext_contract(ext_self)]
+pub trait ContractCallback {
+    fn reload_validators_callback(&mut self, epoch: u64);
+    fn push_snapshot_callback(&mut self, epoch: u64);
+}
+
+#[cfg_attr(not(feature = "embedded"), near_bindgen)]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
     // SETUP CONTRACT STATE
@@ -90,11 +276,25 @@ pub struct Contract {
     trustworthy_threshold: u32,
     node_credibility: UnorderedMap<PublicKey, u32>,
     trustworthy_validators: UnorderedMap<PublicKey, u32>,
+    current_epoch: u64,
+    validator_sets: LookupMap<u64, ValidatorSet>,
+    /// block height of the last selection of the validators
+    selected_at: u64,
+    epoch_length: u64,
+    liveness_step: u32,
+    consecutive_misses: LookupMap<PublicKey, u32>,
+    heartbeat_window: u64,
+    heartbeats: LookupMap<PublicKey, Heartbeat>,
+    penalty_curve: PenaltyCurve,
+    banned: LookupSet<PublicKey>,
+    bls_public_keys: LookupMap<PublicKey, Vec<u8>>,
+    bls_key_owners: LookupMap<Vec<u8>, PublicKey>,
+    operators: LookupMap<PublicKey, AccountId>,
 }
 
+#[cfg(not(feature = "embedded"))]
 #[near_bindgen]
 impl Contract {
-    // ADD CONTRACT METHODS HERE
     #[init]
     pub fn inite(
         cross_contract_id: AccountId,
@@ -105,7 +305,7 @@ impl Contract {
         min_seleted_threshold: u32,
         trustworthy_threshold: u32,
     ) -> Self {
-        Self {
+        Self::new(
             cross_contract_id,
             vc_contract_id,
             initial_credibility_value,
@@ -113,11 +313,156 @@ impl Contract {
             min_trustworthy_ratio,
             min_seleted_threshold,
             trustworthy_threshold,
-            node_credibility: UnorderedMap::new(b'n'),
-            trustworthy_validators: UnorderedMap::new(b't'),
+        )
+    }
+
+    /// `msg-verify contract` keeps the snapshot of the previous epoch until it reads the new one, see its `observe_epoch`
+    #[private]
+    pub fn push_snapshot_callback(&mut self, epoch: u64) {
+        if let PromiseResult::Failed = env::promise_result(0) {
+            env::log_str(&format!(
+                "EVALUATION: snapshot of epoch {} not pushed",
+                epoch
+            ));
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "embedded"), near_bindgen)]
+impl Contract {
+    // ADD CONTRACT METHODS HERE
+    pub fn get_bls_public_key(&self, pk: PublicKey) -> Option<Base64VecU8> {
+        self.bls_public_keys.get(&pk).map(Base64VecU8)
+    }
+
+    pub fn get_current_bls_committee(&self) -> Option<BlsCommittee> {
+        self.validator_sets
+            .get(&self.current_epoch)
+            .map(|validator_set| BlsCommittee {
+                epoch: validator_set.epoch,
+                bls_public_keys: validator_set
+                    .validators
+                    .iter()
+                    .map(|vc| self.bls_public_keys.get(&vc.validator).map(Base64VecU8))
+                    .collect(),
+                operators: validator_set
+                    .validators
+                    .iter()
+                    .map(|vc| self.operators.get(&vc.validator))
+                    .collect(),
+                validators: validator_set.validators,
+            })
+    }
+
+    pub fn is_banned(&self, pk: PublicKey) -> bool {
+        self.banned.contains(&pk)
+    }
+
+    /// set the curve of the penalty for untrusted validators, see `PenaltyCurve`
+    pub fn set_penalty_curve(&mut self, curve: PenaltyCurve) {
+        assert_private();
+        assert!(curve.is_valid(RANGE), "EVALUATION: invalid penalty curve");
+        self.penalty_curve = curve;
+    }
+
+    pub fn get_penalty_curve(&self) -> PenaltyCurve {
+        self.penalty_curve
+    }
+
+    /// set the maximum number of blocks since the last heartbeat for a node to be selected, 0 disables the check
+    pub fn set_heartbeat_window(&mut self, window: u64) {
+        assert_private();
+        self.heartbeat_window = window;
+    }
+
+    pub fn get_liveness(&self, from_index: u64, limit: u64) -> Vec<NodeLiveness> {
+        let keys = self.node_credibility.keys_as_vector();
+        (from_index..std::cmp::min(from_index + limit, self.node_credibility.len()))
+            .map(|index| {
+                let validator = keys.get(index).unwrap();
+                let heartbeat = self.heartbeats.get(&validator);
+                let staleness = heartbeat
+                    .as_ref()
+                    .map(|heartbeat| env::block_height() - heartbeat.block_height);
+                NodeLiveness {
+                    validator,
+                    heartbeat,
+                    staleness,
+                }
+            })
+            .collect()
+    }
+
+    fn is_alive(&self, pk: &PublicKey) -> bool {
+        if self.heartbeat_window == 0 {
+            return true;
+        }
+        match self.heartbeats.get(pk) {
+            Some(heartbeat) => {
+                env::block_height() - heartbeat.block_height <= self.heartbeat_window
+            }
+            None => false,
         }
     }
 
+    /// set the step of the penalty applied for each consecutive missed verification
+    pub fn set_liveness_step(&mut self, value: u32) {
+        assert_private();
+        assert!(value <= RANGE, "EVALUATION: liveness step out of range");
+        self.liveness_step = value;
+    }
+
+    pub fn get_consecutive_misses(&self, pk: PublicKey) -> u32 {
+        self.consecutive_misses.get(&pk).unwrap_or(0)
+    }
+
+    pub fn get_current_epoch(&self) -> u64 {
+        self.current_epoch
+    }
+
+    /// set the number of blocks an epoch lasts before anyone can call `select_validators`
+    pub fn set_epoch_length(&mut self, blocks: u64) {
+        assert_private();
+        self.epoch_length = blocks;
+    }
+
+    pub fn get_epoch_length(&self) -> u64 {
+        self.epoch_length
+    }
+
+    pub fn get_validator_set(&self, epoch: u64) -> Option<ValidatorSet> {
+        self.validator_sets.get(&epoch)
+    }
+
+    pub fn get_current_validator_set(&self) -> Option<ValidatorSet> {
+        self.validator_sets.get(&self.current_epoch)
+    }
+
+    pub fn is_selected(&self, pk: PublicKey, epoch: u64) -> bool {
+        match self.validator_sets.get(&epoch) {
+            Some(validator_set) => validator_set.contains(&pk),
+            None => false,
+        }
+    }
+
+    pub fn reload_validators_callback(&mut self, epoch: u64) {
+        assert_private();
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "EVALUATION: expected one promise result"
+        );
+        let mut validator_set = self
+            .validator_sets
+            .get(&epoch)
+            .expect("EVALUATION: validator set not found");
+        validator_set.status = match env::promise_result(0) {
+            PromiseResult::Successful(_) => ValidatorSetStatus::Acknowledged,
+            _ => ValidatorSetStatus::Failed,
+        };
+        self.validator_sets.insert(&epoch, &validator_set);
+    }
+
     pub fn get_node(&self, from_index: u64, limit: u64) -> Vec<NodeCredibility> {
         let keys = self.node_credibility.keys_as_vector();
         let values = self.node_credibility.values_as_vector();
@@ -130,7 +475,7 @@ impl Contract {
     }
 }
 
-#[near_bindgen]
+#[cfg_attr(not(feature = "embedded"), near_bindgen)]
 impl NodeEvaluation for Contract {
     fn get_nodes_credibility(&self, nodes: Vec<PublicKey>) -> Vec<NodeCredibility> {
         let mut current_node_credibility: Vec<NodeCredibility> = Vec::new();
@@ -145,28 +490,90 @@ impl NodeEvaluation for Contract {
         current_node_credibility
     }
 
+    fn get_nodes_operator(&self, nodes: Vec<PublicKey>) -> Vec<Option<AccountId>> {
+        nodes.iter().map(|node| self.operators.get(node)).collect()
+    }
+
     fn set_initial_credibility(&mut self, value: u32) {
+        assert_private();
         self.initial_credibility_value = value;
     }
 
     // TODO delegation mechanism
     fn register_node(&mut self) {
         let pk = &env::signer_account_pk();
+        assert!(!self.banned.contains(pk), "EVALUATION: node banned");
         match self.node_credibility.get(&pk) {
             None => {
                 self.node_credibility
                     .insert(&pk, &self.initial_credibility_value);
+                self.operators.insert(pk, &env::signer_account_id());
             }
             _ => assert!(false, "already registered"),
         };
     }
 
+    fn register_bls_key(&mut self, public_key: Base64VecU8, proof_of_possession: Base64VecU8) {
+        let pk = env::signer_account_pk();
+        assert!(
+            self.node_credibility.get(&pk).is_some(),
+            "EVALUATION: node not registered"
+        );
+        assert!(
+            self.bls_key_owners
+                .get(&public_key.0)
+                .map_or(true, |owner| owner == pk),
+            "EVALUATION: BLS key already registered"
+        );
+        assert!(
+            bls::verify_proof_of_possession(&public_key.0, pk.as_bytes(), &proof_of_possession.0),
+            "EVALUATION: invalid BLS proof of possession"
+        );
+        if let Some(previous) = self.bls_public_keys.insert(&pk, &public_key.0) {
+            self.bls_key_owners.remove(&previous);
+        }
+        self.bls_key_owners.insert(&public_key.0, &pk);
+    }
+
+    fn heartbeat(&mut self, version: String) {
+        let pk = env::signer_account_pk();
+        assert!(
+            self.node_credibility.get(&pk).is_some(),
+            "EVALUATION: node not registered"
+        );
+        assert!(
+            version.len() <= MAX_VERSION_LEN,
+            "EVALUATION: version too long"
+        );
+        self.heartbeats.insert(
+            &pk,
+            &Heartbeat {
+                block_height: env::block_height(),
+                version,
+            },
+        );
+    }
+
     fn unregister_node(&mut self) {
         let pk = &env::signer_account_pk();
         self.node_credibility.remove(&pk);
+        self.heartbeats.remove(pk);
+        if let Some(key) = self.bls_public_keys.remove(pk) {
+            self.bls_key_owners.remove(&key);
+        }
+        self.consecutive_misses.remove(pk);
+        self.operators.remove(pk);
     }
 
-    fn select_validators(&self) {
+    fn select_validators(&mut self) -> Promise {
+        let caller = env::predecessor_account_id();
+        if caller != env::current_account_id() && caller != self.cross_contract_id {
+            assert!(
+                self.current_epoch == 0
+                    || env::block_height() >= self.selected_at + self.epoch_length,
+                "EVALUATION: current epoch not over"
+            );
+        }
         // let mut trustworthy_sum: u32 = 0;
         // let mut trustworthy_all: u32 = 0;
         // for (_, value) in self.trustworthy_validators.iter() {
@@ -190,13 +597,39 @@ impl NodeEvaluation for Contract {
         //     );
         // let random_selected_num = total_num - credibility_selected_num;
         // let get_block_hight = env::block_height();
-        let validator: Vec<PublicKey> = self.node_credibility.iter().map(|value| value.0).collect();
+        let validators: Vec<NodeCredibility> = self
+            .node_credibility
+            .iter()
+            .filter(|(validator, _)| !self.banned.contains(validator) && self.is_alive(validator))
+            .map(|(validator, credibility_value)| NodeCredibility {
+                validator,
+                credibility_value,
+            })
+            .collect();
+        let validator: Vec<PublicKey> = validators.iter().map(|vc| vc.validator.clone()).collect();
+
+        self.selected_at = env::block_height();
+        self.current_epoch += 1;
+        let epoch = self.current_epoch;
+        let validator_set = ValidatorSet::new(epoch, validators);
+        self.validator_sets.insert(&epoch, &validator_set);
+        // `msg-verify contract` can verify against the snapshot without calling back this contract,
+        // it reads this contract directly when both are built in one
+        #[cfg(not(feature = "embedded"))]
+        self.push_snapshot(validator_set);
+
         ext_cc::reload_validators(
             validator,
             self.cross_contract_id.clone(),
             NO_DEPOSIT,
-            Gas(30_000_000_000_000),
-        );
+            GAS_FOR_RELOAD_VALIDATORS,
+        )
+        .then(ext_self::reload_validators_callback(
+            epoch,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RELOAD_CALLBACK,
+        ))
     }
 
     fn update_nodes(
@@ -204,17 +637,154 @@ impl NodeEvaluation for Contract {
         trusted: Vec<PublicKey>,
         untrusted: Vec<PublicKey>,
         exeception: Vec<(Vec<PublicKey>, u32)>,
+        absent: Vec<PublicKey>,
+        equivocators: Vec<PublicKey>,
     ) {
         assert_eq!(
             env::predecessor_account_id(),
             self.vc_contract_id,
             "EVALUATION: Only call by vc contract"
         );
+        self.record_behavior(trusted, untrusted, exeception, absent, equivocators);
+    }
+
+    fn update_nodes_batch(&mut self, behaviors: Vec<NodeBehavior>) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.vc_contract_id,
+            "EVALUATION: Only call by vc contract"
+        );
+        self.record_behaviors(behaviors);
+    }
+
+    fn punish_fraud(&mut self, trusted: Vec<NodeCredibility>, challenger: PublicKey) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.vc_contract_id,
+            "EVALUATION: Only call by vc contract"
+        );
+        self.record_fraud(trusted, challenger);
+    }
+
+    fn update_storage_date(&mut self, pk: PublicKey, value: u32) {
+        assert_private();
+        self.store_credibility(pk, value);
+    }
+}
+
+impl Contract {
+    /// The initial state, see `inite`
+    pub fn new(
+        cross_contract_id: AccountId,
+        vc_contract_id: AccountId,
+        initial_credibility_value: u32,
+        max_trustworthy_ratio: u32,
+        min_trustworthy_ratio: u32,
+        min_seleted_threshold: u32,
+        trustworthy_threshold: u32,
+    ) -> Self {
+        Self {
+            cross_contract_id,
+            vc_contract_id,
+            initial_credibility_value,
+            max_trustworthy_ratio,
+            min_trustworthy_ratio,
+            min_seleted_threshold,
+            trustworthy_threshold,
+            node_credibility: UnorderedMap::new(storage_key(b'n')),
+            trustworthy_validators: UnorderedMap::new(storage_key(b't')),
+            current_epoch: 0,
+            validator_sets: LookupMap::new(storage_key(b's')),
+            selected_at: 0,
+            epoch_length: DEFAULT_EPOCH_LENGTH,
+            liveness_step: LIVENESS_STEP,
+            consecutive_misses: LookupMap::new(storage_key(b'm')),
+            heartbeat_window: 0,
+            heartbeats: LookupMap::new(storage_key(b'h')),
+            penalty_curve: PenaltyCurve {
+                min_step: DO_EVIL_STEP,
+                max_step: MAX_DO_EVIL_STEP,
+                multiplier: 30000,
+            },
+            banned: LookupSet::new(storage_key(b'b')),
+            bls_public_keys: LookupMap::new(storage_key(b'k')),
+            bls_key_owners: LookupMap::new(storage_key(b'r')),
+            operators: LookupMap::new(storage_key(b'o')),
+        }
+    }
+
+    #[cfg(not(feature = "embedded"))]
+    fn push_snapshot(&self, validator_set: ValidatorSet) {
+        let operators: Vec<Option<AccountId>> = validator_set
+            .validators
+            .iter()
+            .map(|vc| self.operators.get(&vc.validator))
+            .collect();
+        let epoch = validator_set.epoch;
+        ext_vc::set_credibility_snapshot(
+            validator_set,
+            operators,
+            self.vc_contract_id.clone(),
+            NO_DEPOSIT,
+            GAS_FOR_PUSH_SNAPSHOT,
+        )
+        .then(ext_self::push_snapshot_callback(
+            epoch,
+            env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_PUSH_CALLBACK,
+        ));
+    }
+
+    /// Same as `update_storage_date` without checking the caller, for the scoring of the validators
+    fn store_credibility(&mut self, pk: PublicKey, value: u32) {
+        if value < self.min_seleted_threshold {
+            self.trustworthy_validators.remove(&pk);
+        } else {
+            self.trustworthy_validators.insert(&pk, &value);
+        }
+        self.node_credibility.insert(&pk, &value);
+    }
+
+    /// Same as `update_nodes` without checking the caller, for `msg-verify` built in the same contract
+    pub fn record_behavior(
+        &mut self,
+        trusted: Vec<PublicKey>,
+        untrusted: Vec<PublicKey>,
+        exeception: Vec<(Vec<PublicKey>, u32)>,
+        absent: Vec<PublicKey>,
+        equivocators: Vec<PublicKey>,
+    ) {
         let mut credibility_value: u32;
+        // the penalty step grows with the weight of the untrusted group before this update
+        let credibility_sum = |validators: &[PublicKey]| -> u128 {
+            validators
+                .iter()
+                .map(|validator| self.node_credibility.get(validator).unwrap_or(0) as u128)
+                .sum()
+        };
+        let untrusted_credibility = credibility_sum(&untrusted);
+        let untrusted_weight = credibility_weight(
+            untrusted_credibility,
+            untrusted_credibility + credibility_sum(&trusted),
+        );
+        let do_evil_step = correlated_penalty_step(&self.penalty_curve, untrusted_weight);
+        // submitting any copy resets the liveness record
+        for validator in trusted.iter().chain(untrusted.iter()).chain(
+            exeception
+                .iter()
+                .flat_map(|(validators, _)| validators.iter()),
+        ) {
+            self.consecutive_misses.remove(validator);
+        }
         // update current trusted validators credibility
         for validator in trusted {
-            let origin_node_credibility = self.node_credibility.get(&validator).unwrap_or(0);
-            if self.node_credibility.get(&validator).unwrap_or(0) < MIDDLE_CONFIDENCE {
+            // never create the credibility of an unregistered key
+            let origin_node_credibility = match self.node_credibility.get(&validator) {
+                Some(value) => value,
+                None => continue,
+            };
+            if origin_node_credibility < MIDDLE_CONFIDENCE {
                 credibility_value = SUCCESS_STEP * (origin_node_credibility - MIN_CONFIDENCE)
                     / RANGE
                     + origin_node_credibility;
@@ -223,36 +793,95 @@ impl NodeEvaluation for Contract {
                     / RANGE
                     + origin_node_credibility;
             }
-            self.update_storage_date(validator, credibility_value);
+            self.store_credibility(validator, credibility_value);
         }
 
         // update current untrusted validators credibility
         for validator in untrusted {
-            let origin_node_credibility = self.node_credibility.get(&validator).unwrap_or(0);
+            let origin_node_credibility = match self.node_credibility.get(&validator) {
+                Some(value) => value,
+                None => continue,
+            };
             credibility_value = origin_node_credibility
-                - DO_EVIL_STEP * (origin_node_credibility - MIN_CONFIDENCE) / RANGE;
-            self.update_storage_date(validator, credibility_value);
+                - do_evil_step * (origin_node_credibility - MIN_CONFIDENCE) / RANGE;
+            self.store_credibility(validator, credibility_value);
         }
         // update current exeception validators credibility
         for (validators, credibility_weight) in exeception {
             for validator in validators {
-                let origin_node_credibility = self.node_credibility.get(&validator).unwrap_or(0);
+                let origin_node_credibility = match self.node_credibility.get(&validator) {
+                    Some(value) => value,
+                    None => continue,
+                };
                 credibility_value = origin_node_credibility
                     - EXECEPTION_STEP * (origin_node_credibility - MIN_CONFIDENCE) / RANGE
                         * (10000 - credibility_weight)
                         / 10000;
-                self.update_storage_date(validator, credibility_value);
+                self.store_credibility(validator, credibility_value);
             }
         }
+        // ban the equivocators, their credibility drops to the minimum
+        for validator in equivocators {
+            self.consecutive_misses.remove(&validator);
+            self.banned.insert(&validator);
+            if self.node_credibility.get(&validator).is_some() {
+                self.store_credibility(validator, MIN_CONFIDENCE);
+            }
+        }
+        // update current absent validators credibility, the penalty grows with consecutive misses
+        for validator in absent {
+            // unregistered since the validator set was selected
+            let origin_node_credibility = match self.node_credibility.get(&validator) {
+                Some(value) => value,
+                None => continue,
+            };
+            let misses = self.consecutive_misses.get(&validator).unwrap_or(0) + 1;
+            self.consecutive_misses.insert(&validator, &misses);
+            let liveness_step = std::cmp::min(self.liveness_step.saturating_mul(misses), RANGE);
+            credibility_value = origin_node_credibility
+                - liveness_step * (origin_node_credibility - MIN_CONFIDENCE) / RANGE;
+            self.store_credibility(validator, credibility_value);
+        }
     }
 
-    // #[private]
-    fn update_storage_date(&mut self, pk: PublicKey, value: u32) {
-        if value < self.min_seleted_threshold {
-            self.trustworthy_validators.remove(&pk);
-        } else {
-            self.trustworthy_validators.insert(&pk, &value);
+    /// Same as `update_nodes_batch` without checking the caller
+    pub fn record_behaviors(&mut self, behaviors: Vec<NodeBehavior>) {
+        env::log_str(&format!(
+            "EVALUATION: {} verifications scored",
+            behaviors.len()
+        ));
+        for behavior in behaviors {
+            self.record_behavior(
+                behavior.trusted,
+                behavior.untrusted,
+                behavior.exeception,
+                behavior.absent,
+                behavior.equivocators,
+            );
+        }
+    }
+
+    /// Same as `punish_fraud` without checking the caller
+    pub fn record_fraud(&mut self, trusted: Vec<NodeCredibility>, challenger: PublicKey) {
+        for vc in trusted.iter() {
+            let current_credibility = match self.node_credibility.get(&vc.validator) {
+                Some(value) => value,
+                None => continue,
+            };
+            // never more than before the rewarded verification
+            let origin_node_credibility = std::cmp::min(current_credibility, vc.credibility_value);
+            let credibility_value = origin_node_credibility
+                - FRAUD_STEP * (origin_node_credibility - MIN_CONFIDENCE) / RANGE;
+            self.store_credibility(vc.validator.clone(), credibility_value);
+        }
+        if trusted.iter().any(|vc| vc.validator == challenger) {
+            return;
+        }
+        if let Some(origin_node_credibility) = self.node_credibility.get(&challenger) {
+            let credibility_value =
+                CHALLENGER_REWARD_STEP * (MAX_CONFIDENCE - origin_node_credibility) / RANGE
+                    + origin_node_credibility;
+            self.store_credibility(challenger, credibility_value);
         }
-        self.node_credibility.insert(&pk, &value);
     }
 }
@@ -0,0 +1,130 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+
+const PRECISION: u64 = 10_000;
+
+// Penalty curve for validators delivering the untrusted message.
+// The step grows linearly from `min_step` to `max_step` with the credibility weight of the untrusted group,
+// so an isolated fault costs `min_step` and a coordinated attack costs up to `max_step`.
+#[derive(
+    Clone, Copy, PartialEq, BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug,
+)]
+#[serde(tag = "type", crate = "near_sdk::serde")]
+pub struct PenaltyCurve {
+    pub min_step: u32,
+    pub max_step: u32,
+    /// [0~]. Example: 30000 means `max_step` is reached when the untrusted group holds 1/3 of the weight.
+    pub multiplier: u32,
+}
+
+impl PenaltyCurve {
+    pub fn is_valid(&self, range: u32) -> bool {
+        self.min_step <= self.max_step && self.max_step <= range
+    }
+}
+
+/// Weight [0~10000] of `part` in `total`, 0 if `total` is 0.
+/// Totals too large to be multiplied by the precision, like voting powers weighted by stakes, are scaled down first.
+pub fn credibility_weight(part: u128, total: u128) -> u32 {
+    if total == 0 {
+        return 0;
+    }
+    let part = std::cmp::min(part, total);
+    match part.checked_mul(PRECISION as u128) {
+        Some(scaled) => (scaled / total) as u32,
+        None => (part / (total / PRECISION as u128)) as u32,
+    }
+}
+
+/// Penalty step for one untrusted validator when the untrusted group holds `untrusted_weight` [0~10000]
+/// of the credibility in the verification.
+pub fn correlated_penalty_step(curve: &PenaltyCurve, untrusted_weight: u32) -> u32 {
+    let factor = std::cmp::min(
+        PRECISION,
+        untrusted_weight as u64 * curve.multiplier as u64 / PRECISION,
+    );
+    let spread = (curve.max_step - curve.min_step) as u64;
+    curve.min_step + (spread * factor / PRECISION) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVE: PenaltyCurve = PenaltyCurve {
+        min_step: 100,
+        max_step: 1000,
+        multiplier: 30000,
+    };
+
+    #[test]
+    fn test_credibility_weight() {
+        assert_eq!(0, credibility_weight(0, 0));
+        assert_eq!(0, credibility_weight(0, 9));
+        assert_eq!(4444, credibility_weight(4, 9));
+        assert_eq!(10000, credibility_weight(9, 9));
+        assert_eq!(10000, credibility_weight(10, 9));
+        assert_eq!(5000, credibility_weight(u128::MAX / 2, u128::MAX - 1));
+        assert_eq!(10000, credibility_weight(u128::MAX, u128::MAX));
+    }
+
+    #[test]
+    fn test_isolated_fault_costs_min_step() {
+        assert_eq!(100, correlated_penalty_step(&CURVE, 0));
+        // 1% of the weight: 100 + 900 * 300 / 10000
+        assert_eq!(127, correlated_penalty_step(&CURVE, 100));
+    }
+
+    #[test]
+    fn test_penalty_grows_with_weight() {
+        // 10% of the weight: 100 + 900 * 3000 / 10000
+        assert_eq!(370, correlated_penalty_step(&CURVE, 1000));
+        // 20% of the weight: 100 + 900 * 6000 / 10000
+        assert_eq!(640, correlated_penalty_step(&CURVE, 2000));
+        let mut last = 0;
+        for weight in (0..=10000).step_by(100) {
+            let step = correlated_penalty_step(&CURVE, weight);
+            assert!(step >= last);
+            last = step;
+        }
+    }
+
+    #[test]
+    fn test_coordinated_attack_costs_max_step() {
+        assert_eq!(1000, correlated_penalty_step(&CURVE, 3334));
+        assert_eq!(1000, correlated_penalty_step(&CURVE, 4000));
+        assert_eq!(1000, correlated_penalty_step(&CURVE, 10000));
+    }
+
+    #[test]
+    fn test_flat_curve() {
+        let curve = PenaltyCurve {
+            min_step: 200,
+            max_step: 200,
+            multiplier: 30000,
+        };
+        for weight in [0, 1, 3333, 5000, 10000] {
+            assert_eq!(200, correlated_penalty_step(&curve, weight));
+        }
+    }
+
+    #[test]
+    fn test_zero_multiplier() {
+        let curve = PenaltyCurve {
+            multiplier: 0,
+            ..CURVE
+        };
+        assert_eq!(100, correlated_penalty_step(&curve, 10000));
+    }
+
+    #[test]
+    fn test_curve_validation() {
+        assert!(CURVE.is_valid(10000));
+        assert!(!CURVE.is_valid(999));
+        let curve = PenaltyCurve {
+            min_step: 1001,
+            ..CURVE
+        };
+        assert!(!curve.is_valid(10000));
+    }
+}
@@ -1,3 +1,4 @@
 #!/bin/bash
 set -e
-cargo test --package Trusted_Bridge -- --nocapture
\ No newline at end of file
+cargo test --package Trusted_Bridge -- --nocapture
+cargo test --package Trusted_Bridge --features single-contract -- --nocapture
\ No newline at end of file
@@ -0,0 +1,181 @@
+use crate::no_macros::create_message;
+use crate::utils::{init_no_macros as init, register_validators, validator_generate_message};
+use cross_chain::MessageVerify;
+use msg_verify::{AuditEntry, ToHash, VerificationOutcome};
+use near_sdk::serde_json::json;
+use near_sdk::PublicKey;
+use near_sdk_sim::{UserAccount, DEFAULT_GAS};
+
+fn verify(cc: &UserAccount, vc: &UserAccount, msgs: &[MessageVerify], id: u64, percentage: u32) {
+    cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({ "msgs": msgs, "id": id, "percentage": percentage })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+}
+
+fn by_validator(vc: &UserAccount, validator: &PublicKey) -> Vec<AuditEntry> {
+    vc.view(
+        vc.account_id(),
+        "get_audit_by_validator",
+        &json!({ "validator": validator, "from_index": 0u64, "limit": 10u64 })
+            .to_string()
+            .into_bytes(),
+    )
+    .unwrap_json()
+}
+
+// test every verification is kept with its outcome and credibility snapshot
+#[test]
+pub fn simulate_audit_log() {
+    let initail_credibiltiy_value: u32 = 6000u32;
+    let (root, cc, vc, _) = init(1000u32, initail_credibiltiy_value);
+    let (_, validators_pk) = register_validators(&root, 9);
+    let (message_1, message_2) = create_message();
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk[..5], message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &validators_pk[5..],
+        message_2.clone(),
+    ));
+    verify(&cc, &vc, &verify_message, 1, 100);
+    verify(&cc, &vc, &verify_message[..5], 2, 100);
+    verify(&cc, &vc, &verify_message, 3, 9000);
+
+    let entries: Vec<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_by_message_hash",
+            &json!({ "message_hash": message_1.to_hash(Some(1)) })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(1, entries.len());
+    assert_eq!(Some(1), entries[0].id);
+    assert!(entries[0].outcome == VerificationOutcome::Verified);
+    assert_eq!(5555, entries[0].credibility_weight);
+    assert_eq!(2, entries[0].groups);
+    assert_eq!(9, entries[0].snapshot.len());
+    assert_eq!(
+        initail_credibiltiy_value,
+        entries[0].snapshot[0].credibility_value
+    );
+
+    let entry: Option<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_entry",
+            &json!({ "index": 2u64 }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    let entry = entry.unwrap();
+    assert_eq!(Some(3), entry.id);
+    assert!(entry.outcome == VerificationOutcome::ThresholdNotMet);
+    assert_eq!(9000, entry.threshold);
+
+    assert_eq!(3, by_validator(&vc, &validators_pk[0]).len());
+    let entries = by_validator(&vc, &validators_pk[5]);
+    assert_eq!(
+        vec![0, 2],
+        entries.iter().map(|e| e.index).collect::<Vec<u64>>()
+    );
+
+    let entries: Vec<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_by_block_range",
+            &json!({ "from_block": 0u64, "to_block": u64::MAX, "limit": 10u64 })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(3, entries.len());
+    let entries: Vec<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_by_block_range",
+            &json!({
+                "from_block": entries[1].block_height,
+                "to_block": entries[1].block_height,
+                "limit": 10u64
+            })
+            .to_string()
+            .into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(
+        vec![1],
+        entries.iter().map(|e| e.index).collect::<Vec<u64>>()
+    );
+}
+
+// test the oldest verifications beyond the retention are pruned
+#[test]
+pub fn simulate_audit_retention() {
+    let (root, cc, vc, _) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 5);
+    let (message_1, _) = create_message();
+    vc.call(
+        vc.account_id(),
+        "set_audit_retention",
+        &json!({ "retention": 2u64 }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk, message_1.clone());
+    for id in 0..3u64 {
+        verify(&cc, &vc, &verify_message, id, 100);
+    }
+    let entry: Option<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_entry",
+            &json!({ "index": 0u64 }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert!(entry.is_none());
+    let entries = by_validator(&vc, &validators_pk[0]);
+    assert_eq!(
+        vec![1, 2],
+        entries.iter().map(|e| e.index).collect::<Vec<u64>>()
+    );
+    let entries: Vec<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_by_message_hash",
+            &json!({ "message_hash": message_1.to_hash(Some(0)) })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert!(entries.is_empty());
+
+    // lowering the retention prunes on demand
+    vc.call(
+        vc.account_id(),
+        "set_audit_retention",
+        &json!({ "retention": 0u64 }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let pruned: u64 = root
+        .call(
+            vc.account_id(),
+            "prune_audit_log",
+            &json!({ "limit": 10u64 }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert_eq!(2, pruned);
+    assert!(by_validator(&vc, &validators_pk[0]).is_empty());
+}
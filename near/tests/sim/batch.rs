@@ -0,0 +1,243 @@
+use crate::no_macros::create_message;
+use crate::utils::{
+    get_credibility, init_no_macros as init, register_validators, sign_message, total_gas,
+    validator_generate_message,
+};
+use cross_chain::{Message, MessageVerify};
+use msg_verify::{AuditEntry, ToHash, VerificationOutcome};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde_json::json;
+use near_sdk::PublicKey;
+use near_sdk_sim::{ExecutionResult, UserAccount, DEFAULT_GAS};
+
+fn verify_batch(
+    cc: &UserAccount,
+    vc: &UserAccount,
+    batch: &[(u64, Vec<MessageVerify>)],
+    percentage: u32,
+) -> ExecutionResult {
+    cc.call(
+        vc.account_id(),
+        "msg_verify_batch",
+        &json!({ "batch": batch, "percentage": percentage })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+}
+
+// gas burnt by the transaction and every receipt it created
+// test each message of the batch is aggregated on its own
+#[test]
+pub fn simulate_batch_results() {
+    let (root, cc, vc, _) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 5);
+    let (message_1, message_2) = create_message();
+    let mut split_3_2 = validator_generate_message(&validators_pk[..3], message_2.clone());
+    split_3_2.extend(validator_generate_message(
+        &validators_pk[3..],
+        message_1.clone(),
+    ));
+    let mut split_2_2 = validator_generate_message(&validators_pk[..2], message_1.clone());
+    split_2_2.extend(validator_generate_message(
+        &validators_pk[2..4],
+        message_2.clone(),
+    ));
+    let batch = vec![
+        (
+            1u64,
+            validator_generate_message(&validators_pk, message_1.clone()),
+        ),
+        (2u64, split_3_2),
+        (3u64, split_2_2),
+    ];
+    let return_value: Vec<Vec<Message>> = verify_batch(&cc, &vc, &batch, 6000).unwrap_json();
+    assert_eq!(
+        vec![vec![message_1.clone()], vec![message_2.clone()], vec![]],
+        return_value
+    );
+
+    let entries: Vec<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_by_message_hash",
+            &json!({ "message_hash": message_2.to_hash(Some(2)) })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(1, entries.len());
+    assert!(entries[0].outcome == VerificationOutcome::Verified);
+    assert_eq!(6000, entries[0].credibility_weight);
+    assert_eq!(5, entries[0].snapshot.len());
+    let entry: Option<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_entry",
+            &json!({ "index": 2u64 }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    let entry = entry.unwrap();
+    assert_eq!(Some(3), entry.id);
+    assert!(entry.outcome == VerificationOutcome::ThresholdNotMet);
+    assert_eq!(4, entry.snapshot.len());
+
+    // at most 10 messages in a batch
+    let large_batch: Vec<(u64, Vec<MessageVerify>)> = (0..11u64)
+        .map(|id| {
+            (
+                id,
+                validator_generate_message(&validators_pk, message_1.clone()),
+            )
+        })
+        .collect();
+    assert!(!verify_batch(&cc, &vc, &large_batch, 6000).is_ok());
+
+    // only the cross-chain contract can verify
+    assert!(!root
+        .call(
+            vc.account_id(),
+            "msg_verify_batch",
+            &json!({ "batch": batch, "percentage": 6000 })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .is_ok());
+}
+
+// test a batch scores the validators like one `msg_verify` per message, for less gas
+#[test]
+pub fn simulate_batch_gas() {
+    let batch_size: u64 = 10;
+    let (message_1, _) = create_message();
+
+    let (root, cc, vc, ec) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 5);
+    let verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk, message_1.clone());
+    let mut single_gas: u64 = 0;
+    for id in 0..batch_size {
+        let outcome = cc.call(
+            vc.account_id(),
+            "msg_verify",
+            &json!({ "msgs": verify_message, "id": id, "percentage": 5000 })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        );
+        outcome.assert_success();
+        single_gas += total_gas(&outcome);
+    }
+    let single_credibility = get_credibility(&ec, &validators_pk);
+
+    let (root, cc, vc, ec) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 5);
+    let batch: Vec<(u64, Vec<MessageVerify>)> = (0..batch_size)
+        .map(|id| (id, verify_message.clone()))
+        .collect();
+    let outcome = verify_batch(&cc, &vc, &batch, 5000);
+    let return_value: Vec<Vec<Message>> = outcome.unwrap_json();
+    assert!(return_value
+        .iter()
+        .all(|messages| *messages == vec![message_1.clone()]));
+    let batch_gas = total_gas(&outcome);
+    // the credibility is updated once for the whole batch
+    let updates: Vec<String> = outcome
+        .promise_results()
+        .iter()
+        .flatten()
+        .flat_map(|result| result.logs().clone())
+        .filter(|log| log.starts_with("EVALUATION: ") && log.ends_with(" verifications scored"))
+        .collect();
+    assert_eq!(
+        vec![format!("EVALUATION: {} verifications scored", batch_size)],
+        updates
+    );
+
+    assert!(batch_gas < single_gas);
+    assert!(single_credibility == get_credibility(&ec, &validators_pk));
+}
+
+// test the copies of each message are checked against their own signatures, and scored like `msg_verify` would
+#[test]
+pub fn simulate_batch_signed() {
+    let (message_1, message_2) = create_message();
+    let batch_of = |validators_pk: &[PublicKey]| -> Vec<(u64, Vec<MessageVerify>)> {
+        vec![
+            (
+                1u64,
+                validator_generate_message(validators_pk, message_1.clone()),
+            ),
+            (
+                2u64,
+                validator_generate_message(validators_pk, message_2.clone()),
+            ),
+        ]
+    };
+    // the copy of validator4 for the second message is forged by validator0
+    let signatures_of = |validators: &[UserAccount]| -> Vec<Vec<Base64VecU8>> {
+        let mut forged: Vec<Base64VecU8> = validators[..4]
+            .iter()
+            .map(|validator| sign_message(validator, 2, &message_2))
+            .collect();
+        forged.push(sign_message(&validators[0], 2, &message_2));
+        vec![
+            validators
+                .iter()
+                .map(|validator| sign_message(validator, 1, &message_1))
+                .collect(),
+            forged,
+        ]
+    };
+
+    let (root, cc, vc, ec) = init(1000u32, 4000u32);
+    let (validators, validators_pk) = register_validators(&root, 5);
+    for ((id, msgs), signatures) in batch_of(&validators_pk)
+        .into_iter()
+        .zip(signatures_of(&validators))
+    {
+        cc.call(
+            vc.account_id(),
+            "msg_verify",
+            &json!({ "msgs": msgs, "id": id, "percentage": 5000, "signatures": signatures })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .assert_success();
+    }
+    let single_credibility = get_credibility(&ec, &validators_pk);
+
+    let (root, cc, vc, ec) = init(1000u32, 4000u32);
+    let (validators, validators_pk) = register_validators(&root, 5);
+    vc.call(
+        vc.account_id(),
+        "set_signature_required",
+        &json!({ "required": true }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let batch = batch_of(&validators_pk);
+    assert!(!verify_batch(&cc, &vc, &batch, 5000).is_ok());
+    let outcome = cc.call(
+        vc.account_id(),
+        "msg_verify_batch",
+        &json!({ "batch": batch, "percentage": 5000, "signatures": signatures_of(&validators) })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+    let return_value: Vec<Vec<Message>> = outcome.unwrap_json();
+    assert_eq!(vec![vec![message_1], vec![message_2]], return_value);
+    let batch_credibility = get_credibility(&ec, &validators_pk);
+    assert!(single_credibility == batch_credibility);
+    // validator4 is only rewarded for the first message
+    assert!(batch_credibility[4].credibility_value < batch_credibility[3].credibility_value);
+}
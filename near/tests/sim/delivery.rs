@@ -0,0 +1,104 @@
+use crate::no_macros::create_message;
+use crate::utils::{init_no_macros as init, register_validators, validator_generate_message};
+use cross_chain::{Message, MessageVerify};
+use msg_verify::{Delivery, DeliveryStatus, VerificationOutcome};
+use near_sdk::serde_json::json;
+use near_sdk_sim::{UserAccount, DEFAULT_GAS};
+
+fn get_delivery(vc: &UserAccount, id: u64) -> Option<Delivery> {
+    vc.view(
+        vc.account_id(),
+        "get_delivery",
+        &json!({ "id": id }).to_string().into_bytes(),
+    )
+    .unwrap_json()
+}
+
+// test the report is pushed to the cross-chain contract and a failed delivery can be retried
+#[test]
+pub fn simulate_delivery() {
+    let (root, cc, vc, _) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 5);
+    let (message_1, _) = create_message();
+    let verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk, message_1.clone());
+    let verify = |id: u64| -> Vec<Message> {
+        cc.call(
+            vc.account_id(),
+            "msg_verify",
+            &json!({ "msgs": verify_message, "id": id, "percentage": 5000 })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json()
+    };
+    let redeliver = |id: u64| {
+        root.call(
+            vc.account_id(),
+            "redeliver",
+            &json!({ "id": id }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+    };
+
+    // disabled by default
+    assert_eq!(vec![message_1.clone()], verify(1));
+    assert!(get_delivery(&vc, 1).is_none());
+
+    vc.call(
+        vc.account_id(),
+        "set_delivery_method",
+        &json!({ "method": "on_message_verified" })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    // the result is returned even if the cross-chain contract fails to take the report
+    assert_eq!(vec![message_1.clone()], verify(2));
+    let delivery = get_delivery(&vc, 2).unwrap();
+    assert!(delivery.status == DeliveryStatus::Failed);
+    assert_eq!(1, delivery.attempts);
+    assert!(delivery.report.outcome == VerificationOutcome::Verified);
+    assert_eq!(Some(message_1), delivery.report.message);
+
+    let outcome = redeliver(2);
+    let delivered: bool = outcome.unwrap_json();
+    assert!(!delivered);
+    assert!(outcome
+        .promise_results()
+        .iter()
+        .flatten()
+        .flat_map(|result| result.logs().clone())
+        .any(|log| log.starts_with("EVENT_JSON:") && log.contains("\"delivery_failed\"")));
+    assert_eq!(2, get_delivery(&vc, 2).unwrap().attempts);
+    assert!(!redeliver(1).is_ok());
+
+    // a failed delivery is kept past the challenge window until it is delivered
+    vc.call(
+        vc.account_id(),
+        "set_challenge_window",
+        &json!({ "window": 10u64 }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    root.borrow_runtime_mut().produce_blocks(20).unwrap();
+    verify(3);
+    assert!(get_delivery(&vc, 2).unwrap().status == DeliveryStatus::Failed);
+
+    // a failed delivery can not be retried once the delivery is disabled
+    vc.call(
+        vc.account_id(),
+        "set_delivery_method",
+        &json!({ "method": null }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    assert!(!redeliver(2).is_ok());
+}
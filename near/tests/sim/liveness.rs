@@ -0,0 +1,165 @@
+use crate::no_macros::create_message;
+use crate::utils::{
+    get_credibility, init_no_macros as init, register_validators, validator_generate_message,
+};
+use cross_chain::{Message, MessageVerify};
+use near_sdk::serde_json::json;
+use near_sdk::PublicKey;
+use near_sdk_sim::{UserAccount, DEFAULT_GAS};
+use node_evaluation::{NodeLiveness, ValidatorSet};
+
+fn get_consecutive_misses(ec: &UserAccount, pk: &PublicKey) -> u32 {
+    ec.view(
+        ec.account_id(),
+        "get_consecutive_misses",
+        &json!({ "pk": pk }).to_string().into_bytes(),
+    )
+    .unwrap_json()
+}
+
+// selected validators who do not submit are penalized more for each consecutive miss
+#[test]
+pub fn simulate_absent_validators() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let credibility_weight_threshold: u32 = 1000u32;
+    let liveness_step: u32 = 50;
+    let (root, cc, vc, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (_, validators_pk) = register_validators(&root, 5);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+
+    let (message_1, _) = create_message();
+    let verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk[..3], message_1.clone());
+    let return_value: Vec<Message> = cc
+        .call(
+            vc.account_id(),
+            "msg_verify",
+            &json!({ "msgs": verify_message, "percentage": 100})
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert_eq!(message_1, return_value[0]);
+
+    let expect_absent_value: u32 =
+        initail_credibiltiy_value - liveness_step * initail_credibiltiy_value / 10000;
+    for cv in get_credibility(&ec, &validators_pk[3..]) {
+        assert_eq!(expect_absent_value, cv.credibility_value);
+        assert_eq!(1, get_consecutive_misses(&ec, &cv.validator));
+    }
+    for pk in validators_pk[..3].iter() {
+        assert_eq!(0, get_consecutive_misses(&ec, pk));
+    }
+
+    // second miss in a row doubles the liveness step
+    cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({ "msgs": verify_message, "percentage": 100})
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let expect_absent_value: u32 =
+        expect_absent_value - 2 * liveness_step * expect_absent_value / 10000;
+    for cv in get_credibility(&ec, &validators_pk[3..]) {
+        assert_eq!(expect_absent_value, cv.credibility_value);
+        assert_eq!(2, get_consecutive_misses(&ec, &cv.validator));
+    }
+
+    // submitting again resets the record
+    let verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk, message_1.clone());
+    cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({ "msgs": verify_message, "percentage": 100})
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    for pk in validators_pk.iter() {
+        assert_eq!(0, get_consecutive_misses(&ec, pk));
+    }
+}
+
+// nodes without a recent heartbeat are not selected
+#[test]
+pub fn simulate_heartbeat() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let credibility_weight_threshold: u32 = 1000u32;
+    let (root, _, _, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 3);
+    ec.call(
+        ec.account_id(),
+        "set_heartbeat_window",
+        &json!({ "window": 100u64 }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    for validator in validators[..2].iter() {
+        validator
+            .call(
+                ec.account_id(),
+                "heartbeat",
+                &json!({ "version": "0.1.0" }).to_string().into_bytes(),
+                DEFAULT_GAS,
+                0,
+            )
+            .assert_success();
+    }
+
+    let liveness: Vec<NodeLiveness> = ec
+        .view(
+            ec.account_id(),
+            "get_liveness",
+            &json!({"from_index": 0u64, "limit": 10u64})
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(3, liveness.len());
+    for (node, pk) in liveness.iter().zip(validators_pk.iter()) {
+        assert_eq!(*pk, node.validator);
+    }
+    for node in liveness[..2].iter() {
+        assert_eq!("0.1.0", node.heartbeat.as_ref().unwrap().version);
+        assert!(node.staleness.is_some());
+    }
+    assert_eq!(None, liveness[2].heartbeat);
+    assert_eq!(None, liveness[2].staleness);
+
+    // the version is not stored at any length
+    let outcome = validators[2].call(
+        ec.account_id(),
+        "heartbeat",
+        &json!({ "version": "0".repeat(65) })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+    assert!(!outcome.is_ok());
+
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    let validator_set: ValidatorSet = ec
+        .view(
+            ec.account_id(),
+            "get_validator_set",
+            &json!({ "epoch": 1u64 }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(2, validator_set.validators.len());
+    assert!(validator_set.contains(&validators_pk[0]));
+    assert!(validator_set.contains(&validators_pk[1]));
+    assert!(!validator_set.contains(&validators_pk[2]));
+}
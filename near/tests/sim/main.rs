@@ -6,5 +6,19 @@
  * @LastEditors: kay
  */
 
+mod audit;
+mod batch;
+mod delivery;
+mod liveness;
+mod membership;
+mod misbehavior;
 mod no_macros;
+mod optimistic;
+mod policy;
+mod report;
+mod session;
+// `node_evaluation` pushes no snapshot to the contract it is built into
+#[cfg(not(feature = "single-contract"))]
+mod snapshot;
 mod utils;
+mod validator_set;
@@ -0,0 +1,135 @@
+use crate::no_macros::create_message;
+use crate::utils::{
+    get_credibility, init_no_macros as init, register_validators, validator_generate_message,
+};
+use cross_chain::MessageVerify;
+use msg_verify::{ExclusionReason, VerificationOutcome, VerificationReport};
+use near_sdk::serde_json::json;
+use near_sdk::{AccountId, PublicKey};
+use near_sdk_sim::{to_yocto, UserAccount, DEFAULT_GAS};
+use std::str::FromStr;
+
+fn create_node(root: &UserAccount, ec: &UserAccount, name: &str, register: bool) -> PublicKey {
+    let node = root.create_user(AccountId::new_unchecked(name.to_string()), to_yocto("10"));
+    if register {
+        node.call(ec.account_id(), "register_node", b"", DEFAULT_GAS / 2, 0)
+            .assert_success();
+    }
+    PublicKey::from_str(&format!("{}", node.signer.public_key)).unwrap()
+}
+
+fn verify_report(cc: &UserAccount, vc: &UserAccount, msgs: &[MessageVerify]) -> VerificationReport {
+    cc.call(
+        vc.account_id(),
+        "msg_verify_report",
+        &json!({ "msgs": msgs, "percentage": 5000 })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .unwrap_json()
+}
+
+// test copies of validators outside the selected set are dropped before the aggregation and never scored
+#[test]
+pub fn simulate_selected_members() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let (root, cc, vc, ec) = init(1000u32, initail_credibiltiy_value);
+    let (_, validators_pk) = register_validators(&root, 3);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    let unselected = create_node(&root, &ec, "unselected", true);
+    let unregistered = create_node(&root, &ec, "unregistered", false);
+    let (message_1, message_2) = create_message();
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk, message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &[unselected.clone(), unregistered.clone()],
+        message_2,
+    ));
+    let report = verify_report(&cc, &vc, &verify_message);
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert_eq!(Some(message_1), report.message);
+    assert_eq!(1, report.groups.len());
+    assert_eq!(validators_pk, report.groups[0].validators);
+    assert_eq!(3, report.snapshot.len());
+    assert_eq!(2, report.excluded.len());
+    assert_eq!(unselected, report.excluded[0].validator);
+    assert_eq!(unregistered, report.excluded[1].validator);
+    assert!(report
+        .excluded
+        .iter()
+        .all(|excluded| excluded.reason == ExclusionReason::NonMember));
+
+    let credibility_value = get_credibility(&ec, &[unselected, unregistered.clone()]);
+    assert_eq!(
+        initail_credibiltiy_value,
+        credibility_value[0].credibility_value
+    );
+    assert_eq!(0, credibility_value[1].credibility_value);
+    let operators: Vec<Option<AccountId>> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_operator",
+            &json!({ "nodes": [unregistered] }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(vec![None], operators);
+}
+
+// test without a selected validator set only registered validators are aggregated
+#[test]
+pub fn simulate_registered_members() {
+    let (root, cc, vc, ec) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 3);
+    let unregistered = create_node(&root, &ec, "unregistered", false);
+    let (message_1, _) = create_message();
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk, message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &[unregistered.clone()],
+        message_1,
+    ));
+    let report = verify_report(&cc, &vc, &verify_message);
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert_eq!(validators_pk, report.groups[0].validators);
+    assert_eq!(1, report.excluded.len());
+    assert_eq!(unregistered, report.excluded[0].validator);
+    assert!(report.excluded[0].reason == ExclusionReason::NonMember);
+}
+
+// test the credibility settings of `node_evaluation` are only changed by the contract serving them
+#[test]
+pub fn simulate_evaluation_private() {
+    let (root, _, _, ec) = init(1000u32, 4000u32);
+    let (validators, validators_pk) = register_validators(&root, 1);
+    let call = |account: &UserAccount, method: &str, args: near_sdk::serde_json::Value| {
+        account.call(
+            ec.account_id(),
+            method,
+            &args.to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+    };
+    assert!(!call(
+        &validators[0],
+        "set_initial_credibility",
+        json!({ "value": 10000 })
+    )
+    .is_ok());
+    assert!(!call(
+        &validators[0],
+        "update_storage_date",
+        json!({ "pk": validators_pk[0], "value": 10000 })
+    )
+    .is_ok());
+    call(&ec, "set_initial_credibility", json!({ "value": 5000 })).assert_success();
+    call(
+        &ec,
+        "update_storage_date",
+        json!({ "pk": validators_pk[0], "value": 5000 }),
+    )
+    .assert_success();
+}
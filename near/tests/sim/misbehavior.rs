@@ -0,0 +1,263 @@
+use crate::no_macros::create_message;
+use crate::utils::{
+    init_no_macros as init, register_validators, sign_message, validator_generate_message,
+};
+use cross_chain::{Message, MessageVerify};
+use msg_verify::{Attestation, FraudEvidence, ToHash};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde_json::json;
+use near_sdk_sim::{ExecutionResult, DEFAULT_GAS};
+use node_evaluation::NodeCredibility;
+
+// test a validator signing two different copies is excluded and banned
+#[test]
+pub fn simulate_equivocation() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let credibility_weight_threshold: u32 = 1000u32;
+    let (root, cc, vc, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 5);
+    let (message_1, message_2) = create_message();
+    let verify = |id: u64, equivocator: usize, signed: bool| {
+        let mut verify_message: Vec<MessageVerify> =
+            validator_generate_message(&validators_pk, message_1.clone());
+        verify_message.extend(validator_generate_message(
+            &validators_pk[equivocator..equivocator + 1],
+            message_2.clone(),
+        ));
+        let signatures: Option<Vec<Base64VecU8>> = if signed {
+            let mut signatures: Vec<Base64VecU8> = validators
+                .iter()
+                .map(|validator| sign_message(validator, id, &message_1))
+                .collect();
+            signatures.push(sign_message(&validators[equivocator], id, &message_2));
+            Some(signatures)
+        } else {
+            None
+        };
+        cc.call(
+            vc.account_id(),
+            "msg_verify",
+            &json!({ "msgs": verify_message, "id": id, "percentage": 100, "signatures": signatures })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+    };
+    let equivocation = |outcome: &ExecutionResult| {
+        outcome
+            .promise_results()
+            .iter()
+            .flatten()
+            .flat_map(|result| result.logs().clone())
+            .any(|log| log.starts_with("EVENT_JSON:") && log.contains("\"equivocation\""))
+    };
+    let is_banned = |index: usize| -> bool {
+        ec.view(
+            ec.account_id(),
+            "is_banned",
+            &json!({ "pk": validators_pk[index] })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json()
+    };
+    let outcome = verify(1, 0, true);
+    let return_value: Vec<Message> = outcome.unwrap_json();
+    assert_eq!(message_1, return_value[0]);
+    assert!(equivocation(&outcome));
+    assert!(is_banned(0));
+    let credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(0, credibility_value[0].credibility_value);
+    let expect_value: u32 = 100 * initail_credibiltiy_value / 10000 + initail_credibiltiy_value;
+    for cv in credibility_value[1..].iter() {
+        assert_eq!(expect_value, cv.credibility_value);
+    }
+
+    // a banned node can not register again
+    validators[0]
+        .call(ec.account_id(), "unregister_node", b"", DEFAULT_GAS / 2, 0)
+        .assert_success();
+    let outcome = validators[0].call(ec.account_id(), "register_node", b"", DEFAULT_GAS / 2, 0);
+    assert!(!outcome.is_ok());
+
+    // unsigned copies may be forged, they are dropped without banning the validator
+    let outcome = verify(2, 1, false);
+    let return_value: Vec<Message> = outcome.unwrap_json();
+    assert_eq!(message_1, return_value[0]);
+    assert!(!equivocation(&outcome));
+    assert!(!is_banned(1));
+}
+
+// test only the member of a trusted group who signed a conflicting attestation is slashed, if the group signed its copies
+#[test]
+pub fn simulate_fraud_proof() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let credibility_weight_threshold: u32 = 1000u32;
+    let (root, cc, vc, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 6);
+    let (message_1, message_2) = create_message();
+    let id: u64 = 1;
+    let verify = |id: u64, signed: bool| -> Vec<Message> {
+        let verify_message: Vec<MessageVerify> =
+            validator_generate_message(&validators_pk[..5], message_1.clone());
+        let signatures: Option<Vec<Base64VecU8>> = if signed {
+            Some(
+                validators[..5]
+                    .iter()
+                    .map(|validator| sign_message(validator, id, &message_1))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        cc.call(
+            vc.account_id(),
+            "msg_verify",
+            &json!({ "msgs": verify_message, "id": id, "signatures": signatures })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json()
+    };
+    let return_value = verify(id, true);
+    assert_eq!(message_1, return_value[0]);
+
+    // validator0 also signed another message with the same id
+    let evidence = FraudEvidence::ConflictingAttestations(vec![Attestation {
+        validator: validators_pk[0].clone(),
+        message: message_2.clone(),
+        signature: sign_message(&validators[0], id, &message_2),
+    }]);
+    let args = json!({ "message_hash": message_1.to_hash(Some(id)), "evidence": evidence })
+        .to_string()
+        .into_bytes();
+    let proven: bool = validators[5]
+        .call(vc.account_id(), "submit_fraud_proof", &args, DEFAULT_GAS, 0)
+        .unwrap_json();
+    assert!(proven);
+
+    let credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    // only validator0 signed the conflicting message
+    let expect_offender_value: u32 =
+        initail_credibiltiy_value - 2000 * initail_credibiltiy_value / 10000;
+    assert_eq!(
+        expect_offender_value,
+        credibility_value[0].credibility_value
+    );
+    let expect_trusted_value: u32 =
+        100 * initail_credibiltiy_value / 10000 + initail_credibiltiy_value;
+    for cv in credibility_value[1..5].iter() {
+        assert_eq!(expect_trusted_value, cv.credibility_value);
+    }
+    let expect_challenger_value: u32 =
+        200 * (10000 - initail_credibiltiy_value) / 10000 + initail_credibiltiy_value;
+    assert_eq!(
+        expect_challenger_value,
+        credibility_value[5].credibility_value
+    );
+
+    // a validator is punished only once for a message
+    let outcome = validators[5].call(vc.account_id(), "submit_fraud_proof", &args, DEFAULT_GAS, 0);
+    assert!(!outcome.is_ok());
+
+    // an unsigned copy may be attributed to a validator who signed the real message,
+    // so no attestation conflicts with unsigned copies
+    let id: u64 = 2;
+    let return_value = verify(id, false);
+    assert_eq!(message_1, return_value[0]);
+    let evidence = FraudEvidence::ConflictingAttestations(vec![Attestation {
+        validator: validators_pk[1].clone(),
+        message: message_2.clone(),
+        signature: sign_message(&validators[1], id, &message_2),
+    }]);
+    let args = json!({ "message_hash": message_1.to_hash(Some(id)), "evidence": evidence })
+        .to_string()
+        .into_bytes();
+    let outcome = validators[5].call(vc.account_id(), "submit_fraud_proof", &args, DEFAULT_GAS, 0);
+    assert!(!outcome.is_ok());
+}
+
+// test copies with a bad signature are dropped without penalizing their validator
+#[test]
+pub fn simulate_signed_submissions() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let credibility_weight_threshold: u32 = 1000u32;
+    let (root, cc, vc, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 5);
+    vc.call(
+        vc.account_id(),
+        "set_signature_required",
+        &json!({ "required": true }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let (message_1, _) = create_message();
+    let id: u64 = 1;
+    let verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk, message_1.clone());
+
+    let outcome = cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({ "msgs": verify_message, "id": id })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+    assert!(!outcome.is_ok());
+
+    // the copy of validator4 is forged by validator0
+    let mut signatures: Vec<Base64VecU8> = validators[..4]
+        .iter()
+        .map(|validator| sign_message(validator, id, &message_1))
+        .collect();
+    signatures.push(sign_message(&validators[0], id, &message_1));
+    let outcome = cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({ "msgs": verify_message, "id": id, "signatures": signatures })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+    let return_value: Vec<Message> = outcome.unwrap_json();
+    assert_eq!(message_1, return_value[0]);
+    assert!(outcome
+        .logs()
+        .iter()
+        .any(|log| log.starts_with("EVENT_JSON:") && log.contains("\"invalid_signature\"")));
+
+    let credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    let expect_value: u32 = 100 * initail_credibiltiy_value / 10000 + initail_credibiltiy_value;
+    for cv in credibility_value[..4].iter() {
+        assert_eq!(expect_value, cv.credibility_value);
+    }
+    assert_eq!(
+        initail_credibiltiy_value,
+        credibility_value[4].credibility_value
+    );
+}
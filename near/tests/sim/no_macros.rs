@@ -5,10 +5,18 @@
  * @LastEditTime: 2022-03-08 17:59:02
  * @LastEditors: kay
  */
-use crate::utils::{init_no_macros as init, register_validators, validator_generate_message};
+use crate::utils::{
+    bls_public_key, bls_sign, get_credibility, init_no_macros as init, register_bls_key,
+    register_bls_keys, register_validators, validator_generate_message,
+};
+use bls12_381::{G2Affine, G2Projective, Scalar};
 use cross_chain::{Content, Message, MessageKey, MessageVerify, SQOS};
+use msg_verify::{attestation_payload, ToHash, VerifiedRecord};
+use near_sdk::json_types::Base64VecU8;
 use near_sdk::serde_json::json;
+use near_sdk::PublicKey;
 use near_sdk_sim::DEFAULT_GAS;
+use node_evaluation::bls;
 use node_evaluation::NodeCredibility;
 
 pub fn create_message() -> (Message, Message) {
@@ -250,13 +258,79 @@ pub fn simulate_with_untrusted() {
                 .into_bytes(),
         )
         .unwrap_json();
+    // 4 of 9 validators hold more than a third of the weight, the default curve reaches its maximum step
     let expect_untrusted_value: u32 =
-        initail_credibiltiy_value - 200 * initail_credibiltiy_value / 10000;
+        initail_credibiltiy_value - 1000 * initail_credibiltiy_value / 10000;
     for cv in untrusted_credibility_value {
         assert_eq!(expect_untrusted_value, cv.credibility_value);
     }
 }
 
+// test the per-call percentage bounded below by credibility_weight_threshold
+#[test]
+pub fn simulate_percentage_threshold() {
+    let initail_credibiltiy_value: u32 = 6000u32;
+    let (message_1, message_2) = create_message();
+    // 5 of 9 equally credible validators agree, so the weight of message_1 is 5555
+    for (floor, percentage, accepted) in [
+        (1000u32, 5000u32, true),
+        (1000u32, 6000u32, false),
+        (6000u32, 100u32, false),
+    ] {
+        let (root, cc, vc, _) = init(floor, initail_credibiltiy_value);
+        let (_, validators_pk) = register_validators(&root, 9);
+        let mut verify_message: Vec<MessageVerify> =
+            validator_generate_message(&validators_pk[..5], message_1.clone());
+        verify_message.extend(validator_generate_message(
+            &validators_pk[5..],
+            message_2.clone(),
+        ));
+        let return_value: Vec<Message> = cc
+            .call(
+                vc.account_id(),
+                "msg_verify",
+                &json!({ "msgs": verify_message, "percentage": percentage })
+                    .to_string()
+                    .into_bytes(),
+                DEFAULT_GAS,
+                0,
+            )
+            .unwrap_json();
+        let record: Option<VerifiedRecord> = vc
+            .view(
+                vc.account_id(),
+                "get_verified_record",
+                &json!({ "message_hash": message_1.to_hash(None) })
+                    .to_string()
+                    .into_bytes(),
+            )
+            .unwrap_json();
+        if accepted {
+            assert_eq!(vec![message_1.clone()], return_value);
+            assert_eq!(percentage, record.unwrap().threshold);
+        } else {
+            assert!(return_value.is_empty());
+            assert!(record.is_none());
+        }
+    }
+
+    let (root, cc, vc, _) = init(1000u32, initail_credibiltiy_value);
+    let (_, validators_pk) = register_validators(&root, 1);
+    let outcome = cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({
+            "msgs": validator_generate_message(&validators_pk, message_1),
+            "percentage": 10001
+        })
+        .to_string()
+        .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+    assert!(!outcome.is_ok());
+}
+
 // test with untrusted node
 #[test]
 pub fn simulate_with_inconsistency() {
@@ -334,3 +408,314 @@ pub fn simulate_with_inconsistency() {
         assert_eq!(expect_exception_credibity2, cv.credibility_value);
     }
 }
+
+// test the untrusted penalty grows with the weight of the untrusted group
+#[test]
+pub fn simulate_correlated_penalty() {
+    let initail_credibiltiy_value: u32 = 6000u32;
+    let credibility_weight_threshold: u32 = 1000u32;
+    let (root, cc, vc, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (_, validators_pk) = register_validators(&root, 10);
+    ec.call(
+        ec.account_id(),
+        "set_penalty_curve",
+        &json!({ "curve": {"type": "PenaltyCurve", "min_step": 100, "max_step": 1000, "multiplier": 30000} })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let (message_1, message_2) = create_message();
+
+    // isolated fault: 1 of 10 validators
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk[..9], message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &validators_pk[9..],
+        message_2.clone(),
+    ));
+    cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({ "msgs": verify_message, "percentage": 100})
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let isolated_credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk[9..] })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    // weight 1000 => step 100 + 900 * 3000 / 10000
+    let isolated_step: u32 = 370;
+    assert_eq!(
+        initail_credibiltiy_value - isolated_step * initail_credibiltiy_value / 10000,
+        isolated_credibility_value[0].credibility_value
+    );
+
+    // coordinated fault: 4 of 9 validators with the same credibility
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk[..5], message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &validators_pk[5..9],
+        message_2.clone(),
+    ));
+    let trusted_credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk[5..9] })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({ "msgs": verify_message, "percentage": 100})
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let coordinated_credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk[5..9] })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    // weight 4444 => max step
+    let coordinated_step: u32 = 1000;
+    for (before, after) in trusted_credibility_value
+        .iter()
+        .zip(coordinated_credibility_value.iter())
+    {
+        assert_eq!(
+            before.credibility_value - coordinated_step * before.credibility_value / 10000,
+            after.credibility_value
+        );
+    }
+}
+
+// test the default penalty curve punishes a larger untrusted group with a bigger step
+#[test]
+pub fn simulate_default_penalty_curve() {
+    let initail_credibiltiy_value: u32 = 6000u32;
+    let credibility_weight_threshold: u32 = 1000u32;
+    let (root, cc, vc, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (_, validators_pk) = register_validators(&root, 10);
+    let (message_1, message_2) = create_message();
+    // the step of each untrusted validator, relative to its credibility before the verification
+    let untrusted_steps = |trusted: &[PublicKey], untrusted: &[PublicKey]| -> Vec<u32> {
+        let before = get_credibility(&ec, untrusted);
+        let mut verify_message: Vec<MessageVerify> =
+            validator_generate_message(trusted, message_1.clone());
+        verify_message.extend(validator_generate_message(untrusted, message_2.clone()));
+        cc.call(
+            vc.account_id(),
+            "msg_verify",
+            &json!({ "msgs": verify_message, "percentage": 100})
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .assert_success();
+        before
+            .iter()
+            .zip(get_credibility(&ec, untrusted).iter())
+            .map(|(before, after)| {
+                (before.credibility_value - after.credibility_value) * 10000
+                    / before.credibility_value
+            })
+            .collect()
+    };
+
+    // isolated fault: 1 of 10 validators
+    let isolated = untrusted_steps(&validators_pk[..9], &validators_pk[9..]);
+    // coordinated fault: 3 of 9 validators
+    let coordinated = untrusted_steps(&validators_pk[..6], &validators_pk[6..9]);
+    for step in coordinated {
+        assert!(step > isolated[0]);
+    }
+}
+
+// test one aggregate signature of the validators instead of their copies
+#[test]
+pub fn simulate_aggregate_signature() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let credibility_weight_threshold: u32 = 6000u32;
+    let (root, cc, vc, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 3);
+    let secret_keys = register_bls_keys(&ec, &validators);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    let (message_1, _) = create_message();
+    let id: u64 = 1;
+    let epoch: u64 = 1;
+    let payload = attestation_payload(id, &message_1);
+    let aggregate = |signers: &[usize]| -> Base64VecU8 {
+        let mut signature = G2Projective::identity();
+        for index in signers {
+            signature += bls_sign(&secret_keys[*index], &payload, bls::DST_SIGNATURE);
+        }
+        Base64VecU8(G2Affine::from(signature).to_compressed().to_vec())
+    };
+
+    // a message with `sqos.reveal` is only verified in a commit-reveal session
+    let mut revealed = message_1.clone();
+    revealed.sqos.reveal = true;
+    assert!(!cc
+        .call(
+            vc.account_id(),
+            "msg_verify_aggregate",
+            &json!({
+                "message": revealed,
+                "id": id,
+                "epoch": epoch,
+                "signature": aggregate(&[0, 1, 2]),
+                "signers": Base64VecU8(vec![0b111]),
+            })
+            .to_string()
+            .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .is_ok());
+
+    // 1 of 3 validators is below the threshold
+    let return_value: Vec<Message> = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_aggregate",
+            &json!({
+                "message": message_1,
+                "id": id,
+                "epoch": epoch,
+                "signature": aggregate(&[0]),
+                "signers": Base64VecU8(vec![0b001]),
+            })
+            .to_string()
+            .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert_eq!(return_value, Vec::new());
+
+    // the bitmap must match the signature
+    let return_value: Vec<Message> = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_aggregate",
+            &json!({
+                "message": message_1,
+                "id": id,
+                "epoch": epoch,
+                "signature": aggregate(&[0, 2]),
+                "signers": Base64VecU8(vec![0b011]),
+            })
+            .to_string()
+            .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert_eq!(return_value, Vec::new());
+
+    let credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    let return_value: Vec<Message> = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_aggregate",
+            &json!({
+                "message": message_1,
+                "id": id,
+                "epoch": epoch,
+                "signature": aggregate(&[0, 2]),
+                "signers": Base64VecU8(vec![0b101]),
+            })
+            .to_string()
+            .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert_eq!(message_1, return_value[0]);
+
+    let updated_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    for index in [0, 2] {
+        let origin = credibility_value[index].credibility_value;
+        assert_eq!(
+            100 * origin / 10000 + origin,
+            updated_value[index].credibility_value
+        );
+    }
+    // the validator who did not sign is absent for the second time
+    let origin = credibility_value[1].credibility_value;
+    assert_eq!(
+        origin - 2 * 50 * origin / 10000,
+        updated_value[1].credibility_value
+    );
+}
+
+// test a BLS key can only be registered by the node proving its possession
+#[test]
+pub fn simulate_register_bls_key() {
+    let (root, _cc, _vc, ec) = init(6000, 4000);
+    let (validators, validators_pk) = register_validators(&root, 2);
+    let secret_key = Scalar::from(1000u64);
+    register_bls_key(&ec, &validators[0], &validators[0], &secret_key).assert_success();
+
+    // the proof of the first node can not be replayed by the second one
+    assert!(!register_bls_key(&ec, &validators[1], &validators[0], &secret_key).is_ok());
+    // nor can the second node register the key with a proof bound to itself
+    assert!(!register_bls_key(&ec, &validators[1], &validators[1], &secret_key).is_ok());
+    let bls_public_key_of = |index: usize| -> Option<Base64VecU8> {
+        root.view(
+            ec.account_id(),
+            "get_bls_public_key",
+            &json!({ "pk": validators_pk[index] })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json()
+    };
+    assert_eq!(
+        Some(bls_public_key(&secret_key)),
+        bls_public_key_of(0).map(|key| key.0)
+    );
+    assert!(bls_public_key_of(1).is_none());
+
+    // the key is released when the first node registers another one
+    register_bls_key(&ec, &validators[0], &validators[0], &Scalar::from(1001u64)).assert_success();
+    register_bls_key(&ec, &validators[1], &validators[1], &secret_key).assert_success();
+    assert_eq!(
+        Some(bls_public_key(&secret_key)),
+        bls_public_key_of(1).map(|key| key.0)
+    );
+}
@@ -0,0 +1,336 @@
+use crate::no_macros::create_message;
+use crate::policy::set_policy;
+use crate::utils::{init_no_macros as init, register_validators, sign_message};
+use cross_chain::Message;
+use msg_verify::{
+    Attestation, AuditEntry, Bond, ClaimStatus, OptimisticClaim, ToHash, VerificationOutcome,
+};
+use near_sdk::serde_json::json;
+use near_sdk::AccountId;
+use near_sdk_sim::{to_yocto, UserAccount, DEFAULT_GAS};
+use node_evaluation::NodeCredibility;
+
+fn set_optimistic_policy(vc: &UserAccount, window: u64) {
+    set_policy(
+        vc,
+        None,
+        json!({
+            "type": "VerificationPolicy",
+            "credibility_weight_threshold": 5000,
+            "min_validators": 0,
+            "min_operators": 0,
+            "min_credibility": 0,
+            "optimistic_window": window,
+            "voting_power": "Linear",
+            "ambiguity_margin": 0,
+            "commit_blocks": 20,
+            "reveal_blocks": 20,
+        }),
+    );
+}
+
+fn optimistic_claim(vc: &UserAccount, id: u64) -> OptimisticClaim {
+    let claim: Option<OptimisticClaim> = vc
+        .view(
+            vc.account_id(),
+            "get_optimistic_claim",
+            &json!({ "id": id }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    claim.unwrap()
+}
+
+// test the first claim of a bonded validator is accepted after the challenge window
+#[test]
+pub fn simulate_optimistic_unchallenged() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let (root, cc, vc, ec) = init(1000u32, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 3);
+    let (message_1, message_2) = create_message();
+    let id: u64 = 1;
+    let submit = |validator: &UserAccount, message: &Message| {
+        validator.call(
+            vc.account_id(),
+            "submit_optimistic",
+            &json!({ "id": id, "message": message })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+    };
+    // the default policy is not optimistic
+    assert!(!submit(&validators[0], &message_1).is_ok());
+    set_optimistic_policy(&vc, 10);
+    assert!(!submit(&validators[0], &message_1).is_ok());
+    for validator in validators[..2].iter() {
+        validator
+            .call(vc.account_id(), "bond", b"", DEFAULT_GAS, to_yocto("1"))
+            .assert_success();
+    }
+    // a message with `sqos.reveal` is only verified in a commit-reveal session
+    let mut revealed = message_1.clone();
+    revealed.sqos.reveal = true;
+    assert!(!submit(&validators[0], &revealed).is_ok());
+    // a bonded node is not a validator until it registers
+    let outsider = root.create_user(
+        AccountId::new_unchecked("outsider".to_string()),
+        to_yocto("10"),
+    );
+    outsider
+        .call(vc.account_id(), "bond", b"", DEFAULT_GAS, to_yocto("1"))
+        .assert_success();
+    assert!(!submit(&outsider, &message_1).is_ok());
+    let first: bool = submit(&validators[0], &message_1).unwrap_json();
+    assert!(first);
+    let first: bool = submit(&validators[1], &message_2).unwrap_json();
+    assert!(!first);
+    // the bond backs one pending claim
+    assert!(!validators[0]
+        .call(
+            vc.account_id(),
+            "submit_optimistic",
+            &json!({ "id": id + 1, "message": message_1 })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .is_ok());
+
+    let finalize = || {
+        cc.call(
+            vc.account_id(),
+            "finalize_optimistic",
+            &json!({ "id": id }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+    };
+    assert!(!finalize().is_ok());
+    root.borrow_runtime_mut().produce_blocks(11).unwrap();
+    let return_value: Vec<Message> = finalize().unwrap_json();
+    // the accepted claim is audited like any verification
+    let audit: Vec<AuditEntry> = vc
+        .view(
+            vc.account_id(),
+            "get_audit_by_message_hash",
+            &json!({ "message_hash": message_1.to_hash(Some(id)) })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(1, audit.len());
+    assert!(audit[0].outcome == VerificationOutcome::Verified);
+    assert_eq!(vec![message_1], return_value);
+    assert!(optimistic_claim(&vc, id).status == ClaimStatus::Finalized);
+    assert!(!finalize().is_ok());
+
+    // the claimer is rewarded as trusted
+    let credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(
+        100 * initail_credibiltiy_value / 10000 + initail_credibiltiy_value,
+        credibility_value[0].credibility_value
+    );
+    assert_eq!(
+        initail_credibiltiy_value,
+        credibility_value[1].credibility_value
+    );
+    let bond: Option<Bond> = vc
+        .view(
+            vc.account_id(),
+            "get_bond",
+            &json!({ "validator": validators_pk[0] })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(0, bond.unwrap().locked);
+    validators[0]
+        .call(vc.account_id(), "unbond", b"", DEFAULT_GAS, 0)
+        .assert_success();
+}
+
+// test a challenge runs the weighted aggregation and slashes the bond of a wrong claim
+#[test]
+pub fn simulate_optimistic_challenge() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let (root, cc, vc, ec) = init(1000u32, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 4);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    let (message_1, message_2) = create_message();
+    let id: u64 = 1;
+    set_optimistic_policy(&vc, 100);
+    validators[0]
+        .call(vc.account_id(), "bond", b"", DEFAULT_GAS, to_yocto("1"))
+        .assert_success();
+    validators[0]
+        .call(
+            vc.account_id(),
+            "submit_optimistic",
+            &json!({ "id": id, "message": message_2 })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .assert_success();
+
+    let attestations: Vec<Attestation> = (1..4)
+        .map(|index| Attestation {
+            validator: validators_pk[index].clone(),
+            message: message_1.clone(),
+            signature: sign_message(&validators[index], id, &message_1),
+        })
+        .collect();
+    let challenge = |attestations: &[Attestation]| {
+        validators[3].call(
+            vc.account_id(),
+            "challenge_optimistic",
+            &json!({ "id": id, "attestations": attestations })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            to_yocto("1"),
+        )
+    };
+    // a copy of the claimed message is not a challenge
+    let mut copy = attestations[0].clone();
+    copy.message = message_2.clone();
+    copy.signature = sign_message(&validators[1], id, &message_2);
+    assert!(!challenge(&[copy]).is_ok());
+
+    // nor are copies of a message with `sqos.reveal`
+    let mut revealed = message_1.clone();
+    revealed.sqos.reveal = true;
+    let revealed_attestations: Vec<Attestation> = (1..4)
+        .map(|index| Attestation {
+            validator: validators_pk[index].clone(),
+            message: revealed.clone(),
+            signature: sign_message(&validators[index], id, &revealed),
+        })
+        .collect();
+    assert!(!challenge(&revealed_attestations).is_ok());
+
+    // a challenge without a deposit is rejected
+    assert!(!validators[3]
+        .call(
+            vc.account_id(),
+            "challenge_optimistic",
+            &json!({ "id": id, "attestations": attestations })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .is_ok());
+
+    // the challenger gets its deposit back with the bond of the claimer
+    let balance = validators[3].account().unwrap().amount;
+    challenge(&attestations).assert_success();
+    assert!(validators[3].account().unwrap().amount > balance + to_yocto("0.9"));
+    let claim = optimistic_claim(&vc, id);
+    assert!(claim.status == ClaimStatus::Resolved);
+    assert_eq!(Some(message_1.clone()), claim.verified);
+
+    // the claimer is untrusted, the challengers are trusted
+    let credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert!(credibility_value[0].credibility_value < initail_credibiltiy_value);
+    for cv in credibility_value[1..].iter() {
+        assert_eq!(
+            100 * initail_credibiltiy_value / 10000 + initail_credibiltiy_value,
+            cv.credibility_value
+        );
+    }
+
+    let return_value: Vec<Message> = cc
+        .call(
+            vc.account_id(),
+            "finalize_optimistic",
+            &json!({ "id": id }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert_eq!(vec![message_1], return_value);
+}
+
+// test a challenge without a verdict neither slashes the claimer nor keeps the deposit of the challenger
+#[test]
+pub fn simulate_optimistic_undecided() {
+    let (root, _, vc, ec) = init(1000u32, 4000u32);
+    let (validators, validators_pk) = register_validators(&root, 5);
+    let (message_1, message_2) = create_message();
+    let id: u64 = 1;
+    set_optimistic_policy(&vc, 100);
+    validators[0]
+        .call(vc.account_id(), "bond", b"", DEFAULT_GAS, to_yocto("1"))
+        .assert_success();
+    validators[0]
+        .call(
+            vc.account_id(),
+            "submit_optimistic",
+            &json!({ "id": id, "message": message_2 })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .assert_success();
+    let attestations: Vec<Attestation> = (1..3)
+        .map(|index| Attestation {
+            validator: validators_pk[index].clone(),
+            message: message_1.clone(),
+            signature: sign_message(&validators[index], id, &message_1),
+        })
+        .collect();
+    let challenge = || {
+        validators[1].call(
+            vc.account_id(),
+            "challenge_optimistic",
+            &json!({ "id": id, "attestations": attestations })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            to_yocto("1"),
+        )
+    };
+    // without a selected validator set there is nothing to weigh the copies against
+    challenge().assert_success();
+    assert!(optimistic_claim(&vc, id).status == ClaimStatus::Pending);
+
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    // two copies outweigh the claim but not the selected validators who sent none
+    let balance = validators[1].account().unwrap().amount;
+    challenge().assert_success();
+    assert!(validators[1].account().unwrap().amount > balance - to_yocto("0.1"));
+    let claim = optimistic_claim(&vc, id);
+    assert!(claim.status == ClaimStatus::Pending);
+    assert!(claim.challenger.is_none());
+    let bond: Option<Bond> = vc
+        .view(
+            vc.account_id(),
+            "get_bond",
+            &json!({ "validator": validators_pk[0] })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    let bond = bond.unwrap();
+    assert_eq!(to_yocto("1"), bond.amount.0);
+    assert_eq!(1, bond.locked);
+}
@@ -0,0 +1,483 @@
+use crate::no_macros::create_message;
+use crate::utils::{init_no_macros as init, register_validators, validator_generate_message};
+use cross_chain::{Message, MessageVerify};
+use msg_verify::{Route, VerificationOutcome, VerificationPolicy, VerificationReport};
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::{json, Value};
+use near_sdk_sim::{to_yocto, UserAccount, DEFAULT_GAS};
+use node_evaluation::NodeCredibility;
+
+pub fn set_policy(vc: &UserAccount, contract: Option<&str>, policy: Value) {
+    vc.call(
+        vc.account_id(),
+        "set_policy",
+        &json!({
+            "from_chain": "OTHER_CHAIN",
+            "to_chain": "NEAR_CHAIN",
+            "contract": contract,
+            "policy": policy,
+        })
+        .to_string()
+        .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+}
+
+fn resolve_policy(vc: &UserAccount, message: &Message) -> VerificationPolicy {
+    vc.view(
+        vc.account_id(),
+        "resolve_policy",
+        &json!({ "message": message }).to_string().into_bytes(),
+    )
+    .unwrap_json()
+}
+
+// test the destination policy takes precedence over the route policy, then the default policy
+#[test]
+pub fn simulate_policy_precedence() {
+    let (_, _, vc, _) = init(1000u32, 4000u32);
+    let (message_1, message_2) = create_message();
+    set_policy(
+        &vc,
+        None,
+        json!({"type": "VerificationPolicy", "credibility_weight_threshold": 5000, "min_validators": 3, "min_operators": 0, "min_credibility": 0, "optimistic_window": 0, "voting_power": "Linear", "ambiguity_margin": 0, "commit_blocks": 20, "reveal_blocks": 20}),
+    );
+    set_policy(
+        &vc,
+        Some(&message_1.content.contract),
+        json!({"type": "VerificationPolicy", "credibility_weight_threshold": 9000, "min_validators": 5, "min_operators": 0, "min_credibility": 20000, "optimistic_window": 0, "voting_power": "Linear", "ambiguity_margin": 0, "commit_blocks": 20, "reveal_blocks": 20}),
+    );
+    let outcome = vc.view(
+        vc.account_id(),
+        "get_policies",
+        &json!({"from_index": 0u64, "limit": 10u64})
+            .to_string()
+            .into_bytes(),
+    );
+    let policies: Vec<(Route, VerificationPolicy)> = outcome.unwrap_json();
+    assert_eq!(2, policies.len());
+
+    let policy = resolve_policy(&vc, &message_1);
+    assert_eq!(9000, policy.credibility_weight_threshold);
+    assert_eq!(5, policy.min_validators);
+    assert_eq!(20000, policy.min_credibility);
+    let policy = resolve_policy(&vc, &message_2);
+    assert_eq!(5000, policy.credibility_weight_threshold);
+    assert_eq!(3, policy.min_validators);
+
+    let removed: Option<VerificationPolicy> = vc
+        .call(
+            vc.account_id(),
+            "remove_policy",
+            &json!({"from_chain": "OTHER_CHAIN", "to_chain": "NEAR_CHAIN", "contract": null})
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert_eq!(5000, removed.unwrap().credibility_weight_threshold);
+    let policy: Option<VerificationPolicy> = vc
+        .view(
+            vc.account_id(),
+            "get_policy",
+            &json!({"from_chain": "OTHER_CHAIN", "to_chain": "NEAR_CHAIN", "contract": null})
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert!(policy.is_none());
+    let policy = resolve_policy(&vc, &message_2);
+    assert_eq!(1000, policy.credibility_weight_threshold);
+    assert_eq!(0, policy.min_validators);
+    assert_eq!(0, policy.min_credibility);
+}
+
+// test only the admin can manage policies
+#[test]
+pub fn simulate_policy_admin_only() {
+    let (root, _, vc, _) = init(1000u32, 4000u32);
+    let outcome = root.call(
+        vc.account_id(),
+        "set_policy",
+        &json!({
+            "from_chain": "OTHER_CHAIN",
+            "to_chain": "NEAR_CHAIN",
+            "policy": {"type": "VerificationPolicy", "credibility_weight_threshold": 0, "min_validators": 0, "min_operators": 0, "min_credibility": 0, "optimistic_window": 0, "voting_power": "Linear", "ambiguity_margin": 0, "commit_blocks": 20, "reveal_blocks": 20},
+        })
+        .to_string()
+        .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+    assert!(!outcome.is_ok());
+    let outcome = vc.call(
+        vc.account_id(),
+        "set_policy",
+        &json!({
+            "from_chain": "OTHER_CHAIN",
+            "to_chain": "NEAR_CHAIN",
+            "policy": {"type": "VerificationPolicy", "credibility_weight_threshold": 10001, "min_validators": 0, "min_operators": 0, "min_credibility": 0, "optimistic_window": 0, "voting_power": "Linear", "ambiguity_margin": 0, "commit_blocks": 20, "reveal_blocks": 20},
+        })
+        .to_string()
+        .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+    assert!(!outcome.is_ok());
+}
+
+// test a policy can not lower the threshold below the one of the contract
+#[test]
+pub fn simulate_policy_threshold_floor() {
+    let (root, cc, vc, _) = init(7000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 5);
+    let (message_1, message_2) = create_message();
+    set_policy(
+        &vc,
+        None,
+        json!({"type": "VerificationPolicy", "credibility_weight_threshold": 1000, "min_validators": 0, "min_operators": 0, "min_credibility": 0, "optimistic_window": 0, "voting_power": "Linear", "ambiguity_margin": 0, "commit_blocks": 20, "reveal_blocks": 20}),
+    );
+    // 6000 of 10000 over the threshold of the policy, under the one of the contract
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk[..3], message_1);
+    verify_message.extend(validator_generate_message(&validators_pk[3..], message_2));
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": verify_message }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert_eq!(7000, report.threshold);
+    assert!(report.outcome == VerificationOutcome::ThresholdNotMet);
+}
+
+// test the quorum of the policy applies to the trusted group
+#[test]
+pub fn simulate_policy_quorum() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let (message_1, _) = create_message();
+    // 5 validators with 4000 credibility each agree
+    for (min_validators, min_credibility, accepted) in [
+        (5u32, 20000u64, true),
+        (6u32, 0u64, false),
+        (0u32, 20001u64, false),
+    ] {
+        let (root, cc, vc, _) = init(1000u32, initail_credibiltiy_value);
+        let (_, validators_pk) = register_validators(&root, 5);
+        set_policy(
+            &vc,
+            Some(&message_1.content.contract),
+            json!({
+                "type": "VerificationPolicy",
+                "credibility_weight_threshold": 9000,
+                "min_validators": min_validators,
+                "min_operators": 0,
+                "min_credibility": min_credibility,
+                "optimistic_window": 0,
+                "voting_power": "Linear",
+                "ambiguity_margin": 0,
+                "commit_blocks": 20,
+                "reveal_blocks": 20,
+            }),
+        );
+        let verify_message: Vec<MessageVerify> =
+            validator_generate_message(&validators_pk, message_1.clone());
+        let return_value: Vec<Message> = cc
+            .call(
+                vc.account_id(),
+                "msg_verify",
+                &json!({ "msgs": verify_message }).to_string().into_bytes(),
+                DEFAULT_GAS,
+                0,
+            )
+            .unwrap_json();
+        if accepted {
+            assert_eq!(vec![message_1.clone()], return_value);
+        } else {
+            assert!(return_value.is_empty());
+        }
+    }
+}
+
+// test a group reaching the weight but not the count of validators or operators is an exception
+#[test]
+pub fn simulate_policy_distinct_operators() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let (message_1, message_2) = create_message();
+    let (root, cc, vc, ec) = init(1000u32, initail_credibiltiy_value);
+    let (_, validators_pk) = register_validators(&root, 3);
+    set_policy(
+        &vc,
+        None,
+        json!({
+            "type": "VerificationPolicy",
+            "credibility_weight_threshold": 5000,
+            "min_validators": 2,
+            "min_operators": 3,
+            "min_credibility": 0,
+            "optimistic_window": 0,
+            "voting_power": "Linear",
+            "ambiguity_margin": 0,
+            "commit_blocks": 20,
+            "reveal_blocks": 20,
+        }),
+    );
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk[..2], message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &validators_pk[2..],
+        message_2.clone(),
+    ));
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": verify_message }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::QuorumNotMet);
+    assert!(report.message.is_none());
+    assert_eq!(6666, report.groups[0].credibility_weight);
+    assert_eq!(2, report.groups[0].operators);
+
+    // both groups are penalized as an exception by their weight
+    let credibility_value: Vec<NodeCredibility> = ec
+        .view(
+            ec.account_id(),
+            "get_nodes_credibility",
+            &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    let exeception_value = |weight: u32| -> u32 {
+        initail_credibiltiy_value
+            - 100 * initail_credibiltiy_value / 10000 * (10000 - weight) / 10000
+    };
+    assert_eq!(
+        exeception_value(6666),
+        credibility_value[0].credibility_value
+    );
+    assert_eq!(
+        exeception_value(6666),
+        credibility_value[1].credibility_value
+    );
+    assert_eq!(
+        exeception_value(3333),
+        credibility_value[2].credibility_value
+    );
+
+    // a single validator can not carry a message by itself
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": validator_generate_message(&validators_pk[..1], message_1) })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::QuorumNotMet);
+    assert_eq!(1, report.groups[0].operators);
+}
+
+// test the voting power of the policy decides the leading group
+#[test]
+pub fn simulate_policy_voting_power() {
+    let (message_1, message_2) = create_message();
+    for (voting_power, leading) in [
+        (json!("Linear"), message_2.clone()),
+        (json!("StakeWeighted"), message_1.clone()),
+    ] {
+        let (root, cc, vc, _) = init(1000u32, 4000u32);
+        let (validators, validators_pk) = register_validators(&root, 3);
+        set_policy(
+            &vc,
+            None,
+            json!({
+                "type": "VerificationPolicy",
+                "credibility_weight_threshold": 5000,
+                "min_validators": 0,
+                "min_operators": 0,
+                "min_credibility": 0,
+                "optimistic_window": 0,
+                "voting_power": voting_power,
+                "ambiguity_margin": 0,
+                "commit_blocks": 20,
+                "reveal_blocks": 20,
+            }),
+        );
+        for (validator, bond) in validators.iter().zip(["3", "1", "1"]) {
+            validator
+                .call(vc.account_id(), "bond", b"", DEFAULT_GAS, to_yocto(bond))
+                .assert_success();
+        }
+        let mut verify_message: Vec<MessageVerify> =
+            validator_generate_message(&validators_pk[..1], message_1.clone());
+        verify_message.extend(validator_generate_message(
+            &validators_pk[1..],
+            message_2.clone(),
+        ));
+        let report: VerificationReport = cc
+            .call(
+                vc.account_id(),
+                "msg_verify_report",
+                &json!({ "msgs": verify_message }).to_string().into_bytes(),
+                DEFAULT_GAS,
+                0,
+            )
+            .unwrap_json();
+        assert!(report.outcome == VerificationOutcome::Verified);
+        assert_eq!(Some(leading), report.message);
+        assert_eq!(12000, report.total_credibility);
+    }
+
+    // a cap must be a share of the total
+    let (_, _, vc, _) = init(1000u32, 4000u32);
+    let outcome = vc.call(
+        vc.account_id(),
+        "set_policy",
+        &json!({
+            "from_chain": "OTHER_CHAIN",
+            "to_chain": "NEAR_CHAIN",
+            "policy": {"type": "VerificationPolicy", "credibility_weight_threshold": 0, "min_validators": 0, "min_operators": 0, "min_credibility": 0, "optimistic_window": 0, "voting_power": {"Capped": {"cap": 0}}, "ambiguity_margin": 0, "commit_blocks": 20, "reveal_blocks": 20},
+        })
+        .to_string()
+        .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    );
+    assert!(!outcome.is_ok());
+}
+
+// test the voting power follows the route of the leading copy, not the one of the first copy
+#[test]
+pub fn simulate_policy_leading_route() {
+    let (message_1, message_2) = create_message();
+    // the first copy is for message_1, on a route counting the credibility
+    for (bonds, outcome) in [
+        (["1", "1", "1"], VerificationOutcome::Verified),
+        (["3", "1", "1"], VerificationOutcome::Ambiguous),
+    ] {
+        let (root, cc, vc, _) = init(1000u32, 4000u32);
+        let (validators, validators_pk) = register_validators(&root, 3);
+        set_policy(
+            &vc,
+            Some(&message_2.content.contract),
+            json!({
+                "type": "VerificationPolicy",
+                "credibility_weight_threshold": 5000,
+                "min_validators": 0,
+                "min_operators": 0,
+                "min_credibility": 0,
+                "optimistic_window": 0,
+                "voting_power": "StakeWeighted",
+                "ambiguity_margin": 0,
+                "commit_blocks": 20,
+                "reveal_blocks": 20,
+            }),
+        );
+        for (validator, bond) in validators.iter().zip(bonds) {
+            validator
+                .call(vc.account_id(), "bond", b"", DEFAULT_GAS, to_yocto(bond))
+                .assert_success();
+        }
+        let mut verify_message: Vec<MessageVerify> =
+            validator_generate_message(&validators_pk[..1], message_1.clone());
+        verify_message.extend(validator_generate_message(
+            &validators_pk[1..],
+            message_2.clone(),
+        ));
+        let report: VerificationReport = cc
+            .call(
+                vc.account_id(),
+                "msg_verify_report",
+                &json!({ "msgs": verify_message }).to_string().into_bytes(),
+                DEFAULT_GAS,
+                0,
+            )
+            .unwrap_json();
+        // message_2 leads by credibility, the copies are tallied again by stake
+        assert!(report.outcome == outcome);
+        if outcome == VerificationOutcome::Verified {
+            assert_eq!(Some(message_2.clone()), report.message);
+            assert_eq!(U128(to_yocto("2") * 4000), report.groups[0].power);
+        } else {
+            // message_1 leads by stake, on a route counting the credibility
+            assert_eq!(Some(message_1.to_hash(None)), report.message_hash);
+        }
+    }
+}
+
+// test the leading group is an exception if the second one is within the ambiguity margin
+#[test]
+pub fn simulate_policy_ambiguity() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let (message_1, message_2) = create_message();
+    // an exact tie with 2 against 2 validators, 5555 against 4444 with 5 against 4
+    for (validators, split, ambiguity_margin, outcome) in [
+        (4u32, 2usize, 0u32, VerificationOutcome::Ambiguous),
+        (9u32, 5usize, 1000u32, VerificationOutcome::Verified),
+        (9u32, 5usize, 2000u32, VerificationOutcome::Ambiguous),
+    ] {
+        let (root, cc, vc, ec) = init(1000u32, initail_credibiltiy_value);
+        let (_, validators_pk) = register_validators(&root, validators);
+        set_policy(
+            &vc,
+            None,
+            json!({
+                "type": "VerificationPolicy",
+                "credibility_weight_threshold": 1000,
+                "min_validators": 0,
+                "min_operators": 0,
+                "min_credibility": 0,
+                "optimistic_window": 0,
+                "voting_power": "Linear",
+                "ambiguity_margin": ambiguity_margin,
+                "commit_blocks": 20,
+                "reveal_blocks": 20,
+            }),
+        );
+        let mut verify_message: Vec<MessageVerify> =
+            validator_generate_message(&validators_pk[..split], message_1.clone());
+        verify_message.extend(validator_generate_message(
+            &validators_pk[split..],
+            message_2.clone(),
+        ));
+        let report: VerificationReport = cc
+            .call(
+                vc.account_id(),
+                "msg_verify_report",
+                &json!({ "msgs": verify_message }).to_string().into_bytes(),
+                DEFAULT_GAS,
+                0,
+            )
+            .unwrap_json();
+        assert!(report.outcome == outcome);
+        assert_eq!(2, report.groups.len());
+        if outcome == VerificationOutcome::Ambiguous {
+            assert!(report.message.is_none());
+            // every group is an exception, no validator is rewarded
+            let credibility_value: Vec<NodeCredibility> = ec
+                .view(
+                    ec.account_id(),
+                    "get_nodes_credibility",
+                    &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+                )
+                .unwrap_json();
+            assert!(credibility_value
+                .iter()
+                .all(|cv| cv.credibility_value < initail_credibiltiy_value));
+        } else {
+            assert_eq!(Some(message_1.clone()), report.message);
+        }
+    }
+}
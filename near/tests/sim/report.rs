@@ -0,0 +1,251 @@
+use crate::no_macros::create_message;
+use crate::policy::set_policy;
+use crate::utils::{
+    init_no_macros as init, register_validators, sign_message, validator_generate_message,
+};
+use cross_chain::MessageVerify;
+use msg_verify::{ExclusionReason, ToHash, VerificationOutcome, VerificationReport};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde_json::json;
+use near_sdk_sim::DEFAULT_GAS;
+
+// test the report tells the groups, their weights and the threshold applied
+#[test]
+pub fn simulate_report_groups() {
+    let initail_credibiltiy_value: u32 = 6000u32;
+    let (message_1, message_2) = create_message();
+    for (percentage, outcome) in [
+        (5000u32, VerificationOutcome::Verified),
+        (5556u32, VerificationOutcome::ThresholdNotMet),
+    ] {
+        let (root, cc, vc, _) = init(1000u32, initail_credibiltiy_value);
+        let (_, validators_pk) = register_validators(&root, 9);
+        let mut verify_message: Vec<MessageVerify> =
+            validator_generate_message(&validators_pk[..5], message_1.clone());
+        verify_message.extend(validator_generate_message(
+            &validators_pk[5..],
+            message_2.clone(),
+        ));
+        let report: VerificationReport = cc
+            .call(
+                vc.account_id(),
+                "msg_verify_report",
+                &json!({ "msgs": verify_message, "percentage": percentage })
+                    .to_string()
+                    .into_bytes(),
+                DEFAULT_GAS,
+                0,
+            )
+            .unwrap_json();
+        assert!(report.outcome == outcome);
+        assert_eq!(
+            outcome == VerificationOutcome::Verified,
+            report.message == Some(message_1.clone())
+        );
+        assert_eq!(Some(message_1.to_hash(None)), report.message_hash);
+        assert_eq!(percentage, report.threshold);
+        assert_eq!(
+            9 * initail_credibiltiy_value as u64,
+            report.total_credibility
+        );
+        assert_eq!(2, report.groups.len());
+        assert_eq!(validators_pk[..5].to_vec(), report.groups[0].validators);
+        assert_eq!(5555, report.groups[0].credibility_weight);
+        assert_eq!(message_2.to_hash(None), report.groups[1].message_hash);
+        assert_eq!(4444, report.groups[1].credibility_weight);
+        assert!(report.excluded.is_empty());
+    }
+}
+
+// test the report lists the copies dropped for a bad signature or an equivocation
+#[test]
+pub fn simulate_report_excluded() {
+    let (root, cc, vc, _) = init(1000u32, 4000u32);
+    let (validators, validators_pk) = register_validators(&root, 5);
+    let (message_1, message_2) = create_message();
+    let id: u64 = 1;
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk, message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &validators_pk[3..4],
+        message_2.clone(),
+    ));
+    // the copy of validator4 is forged by validator0, validator3 signed two different copies
+    let mut signatures: Vec<Base64VecU8> = validators[..4]
+        .iter()
+        .map(|validator| sign_message(validator, id, &message_1))
+        .collect();
+    signatures.push(sign_message(&validators[0], id, &message_1));
+    signatures.push(sign_message(&validators[3], id, &message_2));
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": verify_message, "id": id, "signatures": signatures })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert_eq!(Some(message_1.to_hash(Some(id))), report.message_hash);
+    assert_eq!(validators_pk[..3].to_vec(), report.groups[0].validators);
+    assert_eq!(2, report.excluded.len());
+    assert_eq!(validators_pk[4], report.excluded[0].validator);
+    assert!(report.excluded[0].reason == ExclusionReason::InvalidSignature);
+    assert_eq!(validators_pk[3], report.excluded[1].validator);
+    assert!(report.excluded[1].reason == ExclusionReason::Equivocation);
+}
+
+// test copies without any credibility are reported unless the bootstrap majority is enabled
+#[test]
+pub fn simulate_report_no_credibility() {
+    let (root, cc, vc, ec) = init(1000u32, 0u32);
+    let (_, validators_pk) = register_validators(&root, 5);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    let (message_1, message_2) = create_message();
+    let mut verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk[..3], message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &validators_pk[3..],
+        message_2.clone(),
+    ));
+    let msg_verify_report = || -> VerificationReport {
+        cc.call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": verify_message }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json()
+    };
+    let report = msg_verify_report();
+    assert!(report.outcome == VerificationOutcome::NoCredibleSubmitters);
+    assert!(report.message.is_none());
+    assert_eq!(0, report.total_credibility);
+    assert_eq!(2, report.groups.len());
+
+    vc.call(
+        vc.account_id(),
+        "set_bootstrap_majority",
+        &json!({ "enabled": true }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    // one vote per validator, 3 of 5 is a strict majority
+    let report = msg_verify_report();
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert!(report.message == Some(message_1.clone()));
+    assert_eq!(5001, report.threshold);
+    assert_eq!(5, report.total_power.0);
+    assert_eq!(6000, report.groups[0].credibility_weight);
+
+    // a tie is no majority
+    let verify_message: Vec<MessageVerify> =
+        validator_generate_message(&validators_pk[..2], message_1.clone())
+            .into_iter()
+            .chain(validator_generate_message(
+                &validators_pk[2..4],
+                message_2.clone(),
+            ))
+            .collect();
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": verify_message }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::ThresholdNotMet);
+
+    // the majority is counted over the selected validators, not over the copies
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": validator_generate_message(&validators_pk[..1], message_1.clone()) })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::ThresholdNotMet);
+    assert!(report.message.is_none());
+    assert_eq!(5, report.total_power.0);
+    assert_eq!(2000, report.groups[0].credibility_weight);
+}
+
+// test credible copies without voting power never fall back to the bootstrap majority
+#[test]
+pub fn simulate_report_no_stake() {
+    let (root, cc, vc, _) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 3);
+    let (message_1, _) = create_message();
+    set_policy(
+        &vc,
+        None,
+        json!({
+            "type": "VerificationPolicy",
+            "credibility_weight_threshold": 5000,
+            "min_validators": 0,
+            "min_operators": 0,
+            "min_credibility": 0,
+            "optimistic_window": 0,
+            "voting_power": "StakeWeighted",
+            "ambiguity_margin": 0,
+            "commit_blocks": 20,
+            "reveal_blocks": 20,
+        }),
+    );
+    vc.call(
+        vc.account_id(),
+        "set_bootstrap_majority",
+        &json!({ "enabled": true }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    // no validator bonded anything
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": validator_generate_message(&validators_pk, message_1) })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::ThresholdNotMet);
+    assert_eq!(12000, report.total_credibility);
+    assert_eq!(0, report.total_power.0);
+}
+
+// test a verification without any copy
+#[test]
+pub fn simulate_report_no_submissions() {
+    let (root, cc, vc, _) = init(1000u32, 4000u32);
+    register_validators(&root, 3);
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": Vec::<MessageVerify>::new() })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::NoSubmissions);
+    assert!(report.groups.is_empty());
+    assert!(report.message_hash.is_none());
+}
@@ -0,0 +1,401 @@
+use crate::no_macros::create_message;
+use crate::policy::set_policy;
+use crate::utils::{
+    get_credibility, init_no_macros as init, register_validators, validator_generate_message,
+};
+use cross_chain::Message;
+use msg_verify::{commitment, Session, SessionStatus, VerificationOutcome, VerificationReport};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde_json::json;
+use near_sdk_sim::{to_yocto, ExecutionResult, UserAccount, DEFAULT_GAS};
+
+fn open_session(cc: &UserAccount, vc: &UserAccount, id: u64, percentage: u32, duration: u64) {
+    cc.call(
+        vc.account_id(),
+        "open_session",
+        &json!({ "id": id, "percentage": percentage, "duration": duration })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+}
+
+fn submit_copy(
+    validator: &UserAccount,
+    vc: &UserAccount,
+    id: u64,
+    message: &Message,
+) -> ExecutionResult {
+    validator.call(
+        vc.account_id(),
+        "submit_copy",
+        &json!({ "id": id, "message": message })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+}
+
+fn commit_copy(validator: &UserAccount, vc: &UserAccount, id: u64, hash: &[u8]) -> ExecutionResult {
+    validator.call(
+        vc.account_id(),
+        "commit_copy",
+        &json!({ "id": id, "hash": Base64VecU8(hash.to_vec()) })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+}
+
+fn reveal_copy(
+    validator: &UserAccount,
+    vc: &UserAccount,
+    id: u64,
+    message: &Message,
+    salt: &[u8],
+) -> ExecutionResult {
+    validator.call(
+        vc.account_id(),
+        "reveal_copy",
+        &json!({ "id": id, "message": message, "salt": Base64VecU8(salt.to_vec()) })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+}
+
+fn get_session(vc: &UserAccount, id: u64) -> Session {
+    let session: Option<Session> = vc
+        .view(
+            vc.account_id(),
+            "get_session",
+            &json!({ "id": id }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    session.unwrap()
+}
+
+// test the session is finalized once the pending validators can not overturn the leading group
+#[test]
+pub fn simulate_session_threshold() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let (root, cc, vc, ec) = init(1000u32, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 5);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    let (message_1, message_2) = create_message();
+    let id: u64 = 1;
+    open_session(&cc, &vc, id, 5000, 100);
+
+    // 2000 and 4000 over the credibility of the selected validators
+    for validator in validators[..2].iter() {
+        submit_copy(validator, &vc, id, &message_1).assert_success();
+        assert!(get_session(&vc, id).status == SessionStatus::Open);
+    }
+    assert!(!submit_copy(&validators[0], &vc, id, &message_1).is_ok());
+    // only the selected validators can submit
+    assert!(!submit_copy(&root, &vc, id, &message_1).is_ok());
+    assert_eq!(2, get_session(&vc, id).submissions.len());
+    let report: VerificationReport = submit_copy(&validators[2], &vc, id, &message_1).unwrap_json();
+    assert!(report.outcome == VerificationOutcome::Verified);
+    let session = get_session(&vc, id);
+    assert!(session.status == SessionStatus::Finalized);
+    assert!(session.report.unwrap().message == Some(message_1.clone()));
+
+    let rewarded = 100 * initail_credibiltiy_value / 10000 + initail_credibiltiy_value;
+    for cv in get_credibility(&ec, &validators_pk) {
+        assert_eq!(
+            if validators_pk[..3].contains(&cv.validator) {
+                rewarded
+            } else {
+                initail_credibiltiy_value
+            },
+            cv.credibility_value
+        );
+    }
+
+    // late copies are scored against the verified message
+    submit_copy(&validators[3], &vc, id, &message_2).assert_success();
+    submit_copy(&validators[4], &vc, id, &message_1).assert_success();
+    assert!(!submit_copy(&validators[0], &vc, id, &message_2).is_ok());
+    assert!(!submit_copy(&root, &vc, id, &message_2).is_ok());
+    let credibility_value = get_credibility(&ec, &validators_pk[3..]);
+    assert!(credibility_value[0].credibility_value < initail_credibiltiy_value);
+    assert_eq!(rewarded, credibility_value[1].credibility_value);
+    assert_eq!(2, get_session(&vc, id).late.len());
+}
+
+// test the pending validators are weighed by the voting power of the policy, not by their credibility
+#[test]
+pub fn simulate_session_voting_power() {
+    let (root, cc, vc, ec) = init(1000u32, 4000u32);
+    let (validators, _) = register_validators(&root, 5);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    set_policy(
+        &vc,
+        None,
+        json!({
+            "type": "VerificationPolicy",
+            "credibility_weight_threshold": 5000,
+            "min_validators": 0,
+            "min_operators": 0,
+            "min_credibility": 0,
+            "optimistic_window": 0,
+            "voting_power": "StakeWeighted",
+            "ambiguity_margin": 0,
+            "commit_blocks": 20,
+            "reveal_blocks": 20,
+        }),
+    );
+    for (index, validator) in validators.iter().enumerate() {
+        let bond = if index < 3 {
+            to_yocto("1")
+        } else {
+            to_yocto("5")
+        };
+        validator
+            .call(vc.account_id(), "bond", b"", DEFAULT_GAS, bond)
+            .assert_success();
+    }
+    let (message_1, message_2) = create_message();
+    let id: u64 = 1;
+    open_session(&cc, &vc, id, 5000, 100);
+
+    // 3 of 5 validators by credibility, but 3 of 13 bonded NEAR
+    for validator in validators[..3].iter() {
+        submit_copy(validator, &vc, id, &message_1).assert_success();
+    }
+    assert!(get_session(&vc, id).status == SessionStatus::Open);
+    // 5 against 3, the last validator can still tip it
+    submit_copy(&validators[3], &vc, id, &message_2).assert_success();
+    assert!(get_session(&vc, id).status == SessionStatus::Open);
+    let report: VerificationReport = submit_copy(&validators[4], &vc, id, &message_2).unwrap_json();
+    assert!(report.outcome == VerificationOutcome::Verified);
+    let session = get_session(&vc, id);
+    assert!(session.status == SessionStatus::Finalized);
+    assert!(session.report.unwrap().message == Some(message_2));
+}
+
+// test the session is finalized with the copies it has once the deadline passed
+#[test]
+pub fn simulate_session_deadline() {
+    let (root, cc, vc, _) = init(1000u32, 4000u32);
+    let (validators, _) = register_validators(&root, 3);
+    let (message_1, message_2) = create_message();
+    let id: u64 = 1;
+    open_session(&cc, &vc, id, 5000, 5);
+    assert!(!submit_copy(&validators[0], &vc, id + 1, &message_1).is_ok());
+
+    // without a selected validator set the session waits for the deadline
+    submit_copy(&validators[0], &vc, id, &message_1).assert_success();
+    submit_copy(&validators[1], &vc, id, &message_1).assert_success();
+    submit_copy(&validators[2], &vc, id, &message_2).assert_success();
+    assert!(get_session(&vc, id).status == SessionStatus::Open);
+
+    let close = || {
+        root.call(
+            vc.account_id(),
+            "close_session",
+            &json!({ "id": id }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+    };
+    assert!(!close().is_ok());
+    root.borrow_runtime_mut().produce_blocks(6).unwrap();
+    let report: VerificationReport = close().unwrap_json();
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert_eq!(Some(message_1), report.message);
+    assert_eq!(6666, report.groups[0].credibility_weight);
+    assert!(get_session(&vc, id).status == SessionStatus::Finalized);
+    assert!(!close().is_ok());
+}
+
+// test a finalized session is removed once its deadline passed the challenge window, an open one is kept
+#[test]
+pub fn simulate_session_expiry() {
+    let (root, cc, vc, _) = init(1000u32, 4000u32);
+    let (validators, _) = register_validators(&root, 1);
+    let (message_1, _) = create_message();
+    vc.call(
+        vc.account_id(),
+        "set_challenge_window",
+        &json!({ "window": 10u64 }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    open_session(&cc, &vc, 1, 5000, 5);
+    open_session(&cc, &vc, 2, 5000, 5);
+    submit_copy(&validators[0], &vc, 1, &message_1).assert_success();
+    root.borrow_runtime_mut().produce_blocks(6).unwrap();
+    root.call(
+        vc.account_id(),
+        "close_session",
+        &json!({ "id": 1u64 }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+
+    root.borrow_runtime_mut().produce_blocks(20).unwrap();
+    open_session(&cc, &vc, 3, 5000, 5);
+    let session: Option<Session> = vc
+        .view(
+            vc.account_id(),
+            "get_session",
+            &json!({ "id": 1u64 }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert!(session.is_none());
+    assert!(get_session(&vc, 2).status == SessionStatus::Open);
+}
+
+// test the copies of a message with `sqos.reveal` are committed, then only the matching reveals are aggregated
+#[test]
+pub fn simulate_session_commit_reveal() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let (root, cc, vc, ec) = init(1000u32, initail_credibiltiy_value);
+    let (validators, validators_pk) = register_validators(&root, 5);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    let (mut message_1, mut message_2) = create_message();
+    message_1.sqos.reveal = true;
+    message_2.sqos.reveal = true;
+    // a message with `sqos.reveal` is only verified in a commit-reveal session
+    assert!(!cc
+        .call(
+            vc.account_id(),
+            "msg_verify",
+            &json!({ "msgs": validator_generate_message(&validators_pk, message_1.clone()) })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .is_ok());
+    set_policy(
+        &vc,
+        None,
+        json!({
+            "type": "VerificationPolicy",
+            "credibility_weight_threshold": 5000,
+            "min_validators": 0,
+            "min_operators": 0,
+            "min_credibility": 0,
+            "optimistic_window": 0,
+            "voting_power": "Linear",
+            "ambiguity_margin": 0,
+            "commit_blocks": 50,
+            "reveal_blocks": 100,
+        }),
+    );
+    let id: u64 = 1;
+    cc.call(
+        vc.account_id(),
+        "open_reveal_session",
+        &json!({ "id": id, "percentage": 5000, "from_chain": "OTHER_CHAIN", "to_chain": "NEAR_CHAIN" })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    let session = get_session(&vc, id);
+    assert_eq!(Some(session.deadline - 100), session.commit_deadline);
+    assert!(!submit_copy(&validators[0], &vc, id, &message_1).is_ok());
+
+    // validator3 copies the commitment of validator0
+    let salt = |index: usize| format!("salt{}", index).into_bytes();
+    let hashes: Vec<[u8; 32]> = (0..3)
+        .map(|index| {
+            let message = if index < 2 { &message_1 } else { &message_2 };
+            commitment(id, message, &validators_pk[index], &salt(index))
+        })
+        .collect();
+    for (index, hash) in hashes.iter().enumerate() {
+        commit_copy(&validators[index], &vc, id, hash).assert_success();
+    }
+    assert!(!commit_copy(&validators[0], &vc, id, &hashes[0]).is_ok());
+    // only the selected validators can commit
+    assert!(!commit_copy(&root, &vc, id, &hashes[0]).is_ok());
+    commit_copy(&validators[3], &vc, id, &hashes[0]).assert_success();
+    assert!(!reveal_copy(&validators[0], &vc, id, &message_1, &salt(0)).is_ok());
+
+    root.borrow_runtime_mut().produce_blocks(50).unwrap();
+    assert!(!commit_copy(&validators[4], &vc, id, &hashes[0]).is_ok());
+    assert!(!reveal_copy(&validators[4], &vc, id, &message_1, &salt(4)).is_ok());
+    assert!(!reveal_copy(&validators[1], &vc, id, &message_1, &salt(0)).is_ok());
+    assert!(!reveal_copy(&validators[2], &vc, id, &message_1, &salt(2)).is_ok());
+    for index in 0..3 {
+        let message = if index < 2 { &message_1 } else { &message_2 };
+        reveal_copy(&validators[index], &vc, id, message, &salt(index)).assert_success();
+    }
+    assert!(!reveal_copy(&validators[3], &vc, id, &message_1, &salt(0)).is_ok());
+    assert!(get_session(&vc, id).status == SessionStatus::Open);
+
+    root.borrow_runtime_mut().produce_blocks(100).unwrap();
+    assert!(!reveal_copy(&validators[3], &vc, id, &message_1, &salt(3)).is_ok());
+    let report: VerificationReport = root
+        .call(
+            vc.account_id(),
+            "close_session",
+            &json!({ "id": id }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert_eq!(Some(message_1.clone()), report.message);
+    assert_eq!(3, get_session(&vc, id).submissions.len());
+
+    // the validator who committed without revealing is absent, like the selected one who never committed
+    let credibility_value = get_credibility(&ec, &validators_pk[3..]);
+    assert!(credibility_value[0].credibility_value < initail_credibiltiy_value);
+    assert_eq!(
+        credibility_value[0].credibility_value,
+        credibility_value[1].credibility_value
+    );
+
+    // the session is finalized once every commitment is revealed
+    let id: u64 = 2;
+    cc.call(
+        vc.account_id(),
+        "open_reveal_session",
+        &json!({ "id": id, "from_chain": "OTHER_CHAIN", "to_chain": "NEAR_CHAIN" })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    for index in 0..2 {
+        commit_copy(
+            &validators[index],
+            &vc,
+            id,
+            &commitment(id, &message_1, &validators_pk[index], &salt(index)),
+        )
+        .assert_success();
+    }
+    root.borrow_runtime_mut().produce_blocks(50).unwrap();
+    reveal_copy(&validators[0], &vc, id, &message_1, &salt(0)).assert_success();
+    assert!(get_session(&vc, id).status == SessionStatus::Open);
+    let silent_value = get_credibility(&ec, &validators_pk[2..]);
+    let report: VerificationReport =
+        reveal_copy(&validators[1], &vc, id, &message_1, &salt(1)).unwrap_json();
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert!(get_session(&vc, id).status == SessionStatus::Finalized);
+
+    // the selected validators who never committed are absent even though the session ended before its deadline
+    let credibility_value = get_credibility(&ec, &validators_pk[2..]);
+    for (before, after) in silent_value.iter().zip(credibility_value.iter()) {
+        assert!(after.credibility_value < before.credibility_value);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::no_macros::create_message;
+use crate::utils::{
+    get_credibility, init_no_macros as init, register_validators, total_gas,
+    validator_generate_message,
+};
+use cross_chain::{Message, MessageVerify};
+use msg_verify::{CredibilitySnapshot, ExclusionReason, VerificationOutcome, VerificationReport};
+use near_sdk::serde_json::json;
+use near_sdk::PublicKey;
+use near_sdk_sim::near_crypto::Signer;
+use near_sdk_sim::{to_yocto, ExecutionResult, UserAccount, DEFAULT_GAS};
+use node_evaluation::NodeCredibility;
+use std::str::FromStr;
+
+fn set_snapshot_max_age(vc: &UserAccount, max_age: Option<u64>) {
+    vc.call(
+        vc.account_id(),
+        "set_snapshot_max_age",
+        &json!({ "max_age": max_age }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+}
+
+// by `node_evaluation` itself, which can start a new epoch at any time
+fn select_validators(ec: &UserAccount) {
+    ec.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+}
+
+fn msg_verify(
+    cc: &UserAccount,
+    vc: &UserAccount,
+    msgs: &[MessageVerify],
+    id: u64,
+) -> ExecutionResult {
+    cc.call(
+        vc.account_id(),
+        "msg_verify",
+        &json!({ "msgs": msgs, "id": id, "percentage": 5000 })
+            .to_string()
+            .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+}
+
+// gas burnt by the transaction and every receipt it created
+fn get_snapshot(vc: &UserAccount) -> Option<CredibilitySnapshot> {
+    vc.view(vc.account_id(), "get_credibility_snapshot", b"")
+        .unwrap_json()
+}
+
+// test the snapshot gives the same verification as reading `node_evaluation contract`, for less gas
+#[test]
+pub fn simulate_snapshot_gas() {
+    let verifications: u64 = 10;
+    let (message_1, _) = create_message();
+    let mut gas: Vec<u64> = Vec::new();
+    let mut credibility: Vec<Vec<NodeCredibility>> = Vec::new();
+    for max_age in [None, Some(1000)] {
+        let (root, cc, vc, ec) = init(1000u32, 4000u32);
+        let (_, validators_pk) = register_validators(&root, 5);
+        select_validators(&ec);
+        set_snapshot_max_age(&vc, max_age);
+        let snapshot = get_snapshot(&vc).unwrap();
+        assert_eq!(1, snapshot.validator_set.epoch);
+        assert_eq!(5, snapshot.validator_set.validators.len());
+        let verify_message = validator_generate_message(&validators_pk, message_1.clone());
+        let mut total: u64 = 0;
+        for id in 0..verifications {
+            let outcome = msg_verify(&cc, &vc, &verify_message, id);
+            let return_value: Vec<Message> = outcome.unwrap_json();
+            assert!(return_value == vec![message_1.clone()]);
+            total += total_gas(&outcome);
+        }
+        gas.push(total);
+        credibility.push(get_credibility(&ec, &validators_pk));
+    }
+    assert!(gas[1] < gas[0]);
+    assert!(credibility[0] == credibility[1]);
+}
+
+// test a stale snapshot falls back to reading `node_evaluation contract` and a new epoch replaces the snapshot
+#[test]
+pub fn simulate_snapshot_stale() {
+    let (root, cc, vc, ec) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 3);
+    let (message_1, _) = create_message();
+    set_snapshot_max_age(&vc, Some(100));
+    select_validators(&ec);
+
+    // the copy of a validator registered after the selection is not in the snapshot
+    let late = root.create_user("late".parse().unwrap(), to_yocto("10"));
+    late.call(ec.account_id(), "register_node", b"", DEFAULT_GAS / 2, 0)
+        .assert_success();
+    let late_pk = PublicKey::from_str(&format!("{}", late.signer.public_key)).unwrap();
+    let mut verify_message = validator_generate_message(&validators_pk, message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &[late_pk.clone()],
+        message_1.clone(),
+    ));
+    let report: VerificationReport = cc
+        .call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": verify_message, "id": 1 })
+                .to_string()
+                .into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json();
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert_eq!(late_pk, report.excluded[0].validator);
+    assert!(report.excluded[0].reason == ExclusionReason::NonMember);
+
+    let fresh = msg_verify(&cc, &vc, &verify_message, 2);
+    fresh.assert_success();
+    root.borrow_runtime_mut().produce_blocks(101).unwrap();
+    let stale = msg_verify(&cc, &vc, &verify_message, 3);
+    let return_value: Vec<Message> = stale.unwrap_json();
+    assert!(return_value == vec![message_1]);
+    assert!(stale.logs().iter().any(|log| log.contains("is stale")));
+    assert!(total_gas(&fresh) < total_gas(&stale));
+
+    select_validators(&ec);
+    let snapshot = get_snapshot(&vc).unwrap();
+    assert_eq!(2, snapshot.validator_set.epoch);
+    assert_eq!(4, snapshot.validator_set.validators.len());
+}
+
+// test a validator penalized after the selection is weighed by its current credibility, not by the snapshot
+#[test]
+pub fn simulate_snapshot_penalized() {
+    let (root, cc, vc, ec) = init(1000u32, 4000u32);
+    let (_, validators_pk) = register_validators(&root, 4);
+    let (message_1, message_2) = create_message();
+    set_snapshot_max_age(&vc, Some(1000));
+    select_validators(&ec);
+    let verify_report = |msgs: &[MessageVerify], id: u64| -> VerificationReport {
+        cc.call(
+            vc.account_id(),
+            "msg_verify_report",
+            &json!({ "msgs": msgs, "id": id }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .unwrap_json()
+    };
+
+    // the last validator is untrusted
+    let mut verify_message = validator_generate_message(&validators_pk[..3], message_1.clone());
+    verify_message.extend(validator_generate_message(
+        &validators_pk[3..],
+        message_2.clone(),
+    ));
+    let report = verify_report(&verify_message, 1);
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert_eq!(16000, report.total_credibility);
+    let snapshot = get_snapshot(&vc).unwrap();
+    assert_eq!(vec![validators_pk[3].clone()], snapshot.penalized);
+
+    let credibility = get_credibility(&ec, &validators_pk);
+    assert!(credibility[3].credibility_value < 4000);
+    let verify_message = validator_generate_message(&validators_pk, message_1.clone());
+    let report = verify_report(&verify_message, 2);
+    assert!(report.outcome == VerificationOutcome::Verified);
+    assert_eq!(
+        credibility
+            .iter()
+            .map(|vc| vc.credibility_value as u64)
+            .sum::<u64>(),
+        report.total_credibility
+    );
+    // the snapshot keeps the credibility read for the penalized validator
+    let snapshot = get_snapshot(&vc).unwrap();
+    assert!(snapshot.penalized.is_empty());
+    assert!(snapshot.validator_set.validators[3] == credibility[3]);
+}
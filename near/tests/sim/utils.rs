@@ -8,27 +8,45 @@
 // use msg_verify::Contract as VC;
 // use node_evaluation::Contract as EC;
 
+use bls12_381::{G1Affine, G2Affine, G2Projective, Scalar};
 use cross_chain::{Message, MessageVerify};
+use msg_verify::attestation_payload;
+use near_sdk::borsh::BorshSerialize;
+use near_sdk::json_types::Base64VecU8;
 use near_sdk::serde_json::json;
 use near_sdk::{AccountId, PublicKey};
-use near_sdk_sim::{init_simulator, to_yocto, UserAccount, DEFAULT_GAS};
+use near_sdk_sim::near_crypto::Signer;
+use near_sdk_sim::{init_simulator, to_yocto, ExecutionResult, UserAccount, DEFAULT_GAS};
+use node_evaluation::bls;
+use node_evaluation::NodeCredibility;
+use std::rc::Rc;
 use std::str::FromStr;
 
 // Load in contract bytes at runtime
+#[cfg(not(feature = "single-contract"))]
 near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
     CC_WASM_BYTES => "../../dante-cross-chain/near/contract/cross_chain/res/cross_chain.wasm",
     VC_WASM_BYTES => "res/msg_verify.wasm",
     EC_WASM_BYTES => "res/node_evaluation.wasm",
 }
+// `msg_verify` built with the logic of `node_evaluation`, see `build.sh`
+#[cfg(feature = "single-contract")]
+near_sdk_sim::lazy_static_include::lazy_static_include_bytes! {
+    CC_WASM_BYTES => "../../dante-cross-chain/near/contract/cross_chain/res/cross_chain.wasm",
+    VC_WASM_BYTES => "res/msg_verify_single.wasm",
+}
 
 const VC_ID: &str = "vc";
+#[cfg(not(feature = "single-contract"))]
 const EC_ID: &str = "ec";
+#[cfg(feature = "single-contract")]
+const EC_ID: &str = VC_ID;
 const CC_ID: &str = "cc";
 
 pub fn init_no_macros(
     credibility_weight_threshold: u32,
     initial_crediblity_value: u32,
-) -> (UserAccount, UserAccount, UserAccount, UserAccount) {
+) -> (UserAccount, UserAccount, Rc<UserAccount>, Rc<UserAccount>) {
     let root = init_simulator(None);
     let cc = root.deploy(&CC_WASM_BYTES, CC_ID.parse().unwrap(), to_yocto("2000"));
     cc.call(
@@ -46,7 +64,7 @@ pub fn init_no_macros(
     )
     .assert_success();
 
-    let vc = root.deploy(&VC_WASM_BYTES, VC_ID.parse().unwrap(), to_yocto("2000"));
+    let vc = Rc::new(root.deploy(&VC_WASM_BYTES, VC_ID.parse().unwrap(), to_yocto("2000")));
 
     vc.call(
         VC_ID.parse().unwrap(),
@@ -62,8 +80,25 @@ pub fn init_no_macros(
         0,
     )
     .assert_success();
+    // signatures are required by default, most simulations submit unsigned copies
+    let required: bool = vc
+        .view(VC_ID.parse().unwrap(), "get_signature_required", b"")
+        .unwrap_json();
+    assert!(required);
+    vc.call(
+        VC_ID.parse().unwrap(),
+        "set_signature_required",
+        &json!({ "required": false }).to_string().into_bytes(),
+        DEFAULT_GAS / 2,
+        0,
+    )
+    .assert_success();
 
-    let ec = root.deploy(&EC_WASM_BYTES, EC_ID.parse().unwrap(), to_yocto("2000"));
+    // a single contract is both `vc` and `ec`
+    #[cfg(not(feature = "single-contract"))]
+    let ec = Rc::new(root.deploy(&EC_WASM_BYTES, EC_ID.parse().unwrap(), to_yocto("2000")));
+    #[cfg(feature = "single-contract")]
+    let ec = Rc::clone(&vc);
     ec.call(
         EC_ID.parse().unwrap(),
         "inite",
@@ -123,3 +158,81 @@ pub fn validator_generate_message(
     }
     verify_msg
 }
+
+pub fn sign_message(validator: &UserAccount, id: u64, message: &Message) -> Base64VecU8 {
+    let signature = validator
+        .signer
+        .sign(&attestation_payload(id, message))
+        .try_to_vec()
+        .unwrap();
+    // skip the curve type
+    Base64VecU8(signature[1..].to_vec())
+}
+
+pub fn bls_public_key(secret_key: &Scalar) -> Vec<u8> {
+    G1Affine::from(G1Affine::generator() * secret_key)
+        .to_compressed()
+        .to_vec()
+}
+
+pub fn bls_sign(secret_key: &Scalar, message: &[u8], dst: &[u8]) -> G2Projective {
+    bls::hash_to_g2(message, dst) * secret_key
+}
+
+/// Register the BLS key of `secret_key` for `validator`, with a proof of possession bound to the NEAR key of `owner`
+pub fn register_bls_key(
+    ec: &UserAccount,
+    validator: &UserAccount,
+    owner: &UserAccount,
+    secret_key: &Scalar,
+) -> ExecutionResult {
+    let public_key = bls_public_key(secret_key);
+    let owner = PublicKey::from_str(&format!("{}", owner.signer.public_key)).unwrap();
+    let proof_of_possession = G2Affine::from(bls_sign(
+        secret_key,
+        &bls::proof_of_possession_payload(&public_key, owner.as_bytes()),
+        bls::DST_POP,
+    ));
+    validator.call(
+        ec.account_id(),
+        "register_bls_key",
+        &json!({
+            "public_key": Base64VecU8(public_key),
+            "proof_of_possession": Base64VecU8(proof_of_possession.to_compressed().to_vec()),
+        })
+        .to_string()
+        .into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+}
+
+pub fn register_bls_keys(ec: &UserAccount, validators: &[UserAccount]) -> Vec<Scalar> {
+    let mut secret_keys: Vec<Scalar> = Vec::new();
+    for (index, validator) in validators.iter().enumerate() {
+        let secret_key = Scalar::from(index as u64 + 1000);
+        register_bls_key(ec, validator, validator, &secret_key).assert_success();
+        secret_keys.push(secret_key);
+    }
+    secret_keys
+}
+
+/// The credibility of `validators_pk` read from `node_evaluation contract`
+pub fn get_credibility(ec: &UserAccount, validators_pk: &[PublicKey]) -> Vec<NodeCredibility> {
+    ec.view(
+        ec.account_id(),
+        "get_nodes_credibility",
+        &json!({ "nodes": validators_pk }).to_string().into_bytes(),
+    )
+    .unwrap_json()
+}
+
+/// The gas burnt by `outcome` and every receipt it spawned
+pub fn total_gas(outcome: &ExecutionResult) -> u64 {
+    outcome
+        .promise_results()
+        .iter()
+        .flatten()
+        .map(|result| result.gas_burnt().0)
+        .sum()
+}
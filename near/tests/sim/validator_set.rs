@@ -0,0 +1,122 @@
+use crate::utils::{init_no_macros as init, register_validators};
+use near_sdk::serde_json::json;
+use near_sdk_sim::{UserAccount, DEFAULT_GAS};
+use node_evaluation::{ValidatorSet, ValidatorSetStatus};
+
+#[test]
+pub fn simulate_select_validators_snapshot() {
+    let initail_credibiltiy_value: u32 = 4000u32;
+    let credibility_weight_threshold: u32 = 1000u32;
+    let (root, _, _, ec) = init(credibility_weight_threshold, initail_credibiltiy_value);
+    let (_, validators_pk) = register_validators(&root, 3);
+    root.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+
+    let current_epoch: u64 = ec
+        .view(ec.account_id(), "get_current_epoch", b"")
+        .unwrap_json();
+    assert_eq!(1, current_epoch);
+
+    let validator_set: ValidatorSet = ec
+        .view(
+            ec.account_id(),
+            "get_validator_set",
+            &json!({ "epoch": current_epoch }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(current_epoch, validator_set.epoch);
+    assert_eq!(ValidatorSetStatus::Acknowledged, validator_set.status);
+    assert_eq!(64, validator_set.commitment.len());
+    for (vc, pk) in validator_set.validators.iter().zip(validators_pk.iter()) {
+        assert_eq!(*pk, vc.validator);
+        assert_eq!(initail_credibiltiy_value, vc.credibility_value);
+    }
+
+    let selected: bool = ec
+        .view(
+            ec.account_id(),
+            "is_selected",
+            &json!({ "pk": validators_pk[0], "epoch": current_epoch })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert!(selected);
+    let selected: bool = ec
+        .view(
+            ec.account_id(),
+            "is_selected",
+            &json!({ "pk": validators_pk[0], "epoch": current_epoch + 1 })
+                .to_string()
+                .into_bytes(),
+        )
+        .unwrap_json();
+    assert!(!selected);
+
+    // a new selection never rewrites the previous snapshot
+    ec.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+        .assert_success();
+    let previous_set: ValidatorSet = ec
+        .view(
+            ec.account_id(),
+            "get_validator_set",
+            &json!({ "epoch": current_epoch }).to_string().into_bytes(),
+        )
+        .unwrap_json();
+    assert_eq!(validator_set, previous_set);
+}
+
+// test anyone can only start a new epoch once the current one lasted `epoch_length` blocks, and old sets are pruned
+#[test]
+pub fn simulate_select_validators_epoch_length() {
+    let (root, _, _, ec) = init(1000u32, 4000u32);
+    register_validators(&root, 3);
+    let select = |account: &UserAccount| {
+        account.call(ec.account_id(), "select_validators", b"", DEFAULT_GAS, 0)
+    };
+    let current_epoch = || -> u64 {
+        ec.view(ec.account_id(), "get_current_epoch", b"")
+            .unwrap_json()
+    };
+    select(&root).assert_success();
+    assert!(!select(&root).is_ok());
+    assert!(!root
+        .call(
+            ec.account_id(),
+            "set_epoch_length",
+            &json!({ "blocks": 10 }).to_string().into_bytes(),
+            DEFAULT_GAS,
+            0,
+        )
+        .is_ok());
+    ec.call(
+        ec.account_id(),
+        "set_epoch_length",
+        &json!({ "blocks": 10 }).to_string().into_bytes(),
+        DEFAULT_GAS,
+        0,
+    )
+    .assert_success();
+    root.borrow_runtime_mut().produce_blocks(10).unwrap();
+    select(&root).assert_success();
+    assert_eq!(2, current_epoch());
+    assert!(!select(&root).is_ok());
+
+    // the validator set of every epoch is kept
+    for _ in 0..7 {
+        select(&ec).assert_success();
+    }
+    assert_eq!(9, current_epoch());
+    let validator_set = |epoch: u64| -> Option<ValidatorSet> {
+        ec.view(
+            ec.account_id(),
+            "get_validator_set",
+            &json!({ "epoch": epoch }).to_string().into_bytes(),
+        )
+        .unwrap_json()
+    };
+    for epoch in 1..=9 {
+        assert_eq!(epoch, validator_set(epoch).unwrap().epoch);
+    }
+    assert!(validator_set(10).is_none());
+}